@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context as _};
+use clap::Parser;
+
+use cadillac_solver::*;
+
+/// 解手順をエミュレータ用入力ムービー (.fm2 / .bk2) として書き出す (実験的)。
+///
+/// 入力タイミングのモデルは未検証で、ムービーはレベル開始時から始まる。
+/// .fm2 のヘッダの romChecksum と guid はダミー値なので、再生前に書き換えること。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// ゲームレベル。
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: u8,
+
+    /// 開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 初期山札配列メモリダンプのパス。
+    path_pile: PathBuf,
+
+    /// 解手順 (例: "[A, B, C]")。
+    solution: Solution,
+
+    /// 出力先のパス。拡張子 (fm2 または bk2) で形式を判別する。
+    path_movie: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let level = Level::from_inner(cli.level).unwrap();
+
+    let pile = std::fs::read_to_string(&cli.path_pile).with_context(|| {
+        format!(
+            "初期山札配列メモリダンプ '{}' を読み取れない",
+            cli.path_pile.display()
+        )
+    })?;
+    let pile = CardPile::parse_memory_initial(pile)?;
+
+    let movie = InputMovie::from_solution(
+        &InputModel::default(),
+        level,
+        cli.money,
+        pile,
+        &cli.solution,
+        &cli.frame_model,
+    )?;
+    eprintln!("frame={}", movie.len());
+
+    let ext = cli.path_movie.extension().and_then(|ext| ext.to_str());
+    let file = std::fs::File::create(&cli.path_movie)
+        .with_context(|| format!("'{}' を作成できない", cli.path_movie.display()))?;
+    match ext {
+        Some("fm2") => movie.write_fm2(std::io::BufWriter::new(file))?,
+        Some("bk2") => movie.write_bk2(file)?,
+        _ => bail!("未知の拡張子: '{}'", cli.path_movie.display()),
+    }
+
+    Ok(())
+}
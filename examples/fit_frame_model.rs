@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Parser;
+
+use cadillac_solver::*;
+
+/// 記録されたプレイのトレースからフレームコストのモデルのパラメータを最小二乗法で推定する。
+///
+/// トレースは 1 行 1 着手で、"盤面\tツモ\t列\tフレーム数" 形式。
+/// 盤面は着手前のもので、上の行から順に '/' 区切りで書く。空行および '#' で始まる行は無視する。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// トレース中で一度も効かないパラメータに使う値 (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// トレースファイルのパス。
+    path_trace: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let trace = std::fs::read_to_string(&cli.path_trace)
+        .with_context(|| format!("トレース '{}' を読み取れない", cli.path_trace.display()))?;
+
+    let events = trace
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            line.parse::<TraceEvent>()
+                .with_context(|| format!("トレース {} 行目", i + 1))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let fit = fit_frame_model(&events, &cli.frame_model)?;
+
+    for (name, value) in fit.params() {
+        println!("{name}\t{value:.3}");
+    }
+    println!();
+
+    println!("kind\tcount\tmean\trms\tmax_abs");
+    for stats in fit.residuals() {
+        println!(
+            "{:?}\t{}\t{:.3}\t{:.3}\t{:.3}",
+            stats.kind(),
+            stats.count(),
+            stats.mean(),
+            stats.rms(),
+            stats.max_abs()
+        );
+    }
+    println!();
+
+    println!("--frame-model {}", fit.model());
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Parser;
+
+use cadillac_solver::*;
+
+/// 入力ムービー (.fm2) から解手順を復元し、シミュレータ上のフレーム数と比較する。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// ゲームレベル。
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: u8,
+
+    /// 開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// ムービー上でレベルが開始するフレーム。
+    #[arg(long, default_value_t = 0)]
+    start_frame: usize,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 初期山札配列メモリダンプのパス。
+    path_pile: PathBuf,
+
+    /// 入力ムービー (.fm2) のパス。
+    path_movie: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let level = Level::from_inner(cli.level).unwrap();
+
+    let pile = std::fs::read_to_string(&cli.path_pile).with_context(|| {
+        format!(
+            "初期山札配列メモリダンプ '{}' を読み取れない",
+            cli.path_pile.display()
+        )
+    })?;
+    let pile = CardPile::parse_memory_initial(pile)?;
+
+    let movie = std::fs::read_to_string(&cli.path_movie)
+        .with_context(|| format!("ムービー '{}' を読み取れない", cli.path_movie.display()))?;
+    let movie = InputMovie::parse_fm2(movie)?;
+
+    let analysis = movie.analyze(
+        &InputModel::default(),
+        level,
+        cli.money,
+        pile,
+        cli.start_frame,
+        &cli.frame_model,
+    )?;
+
+    for (ply, (frame, frame_sim)) in
+        std::iter::zip(analysis.drop_frames(), analysis.drop_frames_sim()).enumerate()
+    {
+        println!("ply {ply}: movie={frame} sim={frame_sim}");
+    }
+    println!("solution={}", analysis.solution());
+    println!("money={}", analysis.state().money());
+    println!("frame_sim={}", analysis.frame_sim());
+    println!("frame_movie={}", analysis.frame_movie());
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context as _};
+use clap::Parser;
+
+use cadillac_solver::*;
+
+/// レベル開始の遅延フレーム数ごとに採取した初期山札を簡易探索し、遅延込みの推定最終フレーム数でランク付けする。
+///
+/// 原作の山札生成ルーチンは未解析なので山札の生成は行わず、調べたい遅延ごとに採取したメモリダンプを与える必要がある。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// ゲームレベル。
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: u8,
+
+    /// 開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// レベルクリアに必要な所持金。省略時は原作で確認済みの値を用いる (レベル 9, 10 のみ)。
+    #[arg(long)]
+    money_required: Option<Money>,
+
+    /// 簡易探索におけるビーム幅。
+    #[arg(long, default_value_t = 10_000)]
+    beam_width: usize,
+
+    /// 評価関数用の乱数シード。
+    #[arg(long, default_value_t = 0)]
+    rng_seed: u64,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 中盤探索の評価関数のパラメータファイルのパス。省略時はデフォルトのパラメータを用いる。
+    #[arg(long)]
+    evaluator: Option<PathBuf>,
+
+    /// 初期山札配列メモリダンプを 1 行 1 つずつ、遅延 0 フレームから順に書いたファイルのパス。
+    path_piles: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let level = Level::from_inner(cli.level).unwrap();
+    let cond = level
+        .rules()
+        .clear_condition(cli.money_required)
+        .with_context(|| {
+            format!(
+                "レベル {} の必要所持金は未確認なので --money-required で指定すること",
+                cli.level
+            )
+        })?;
+
+    let evaluator = ParamEvaluator::load(cli.evaluator.as_deref())?;
+
+    let piles = std::fs::read_to_string(&cli.path_piles).with_context(|| {
+        format!(
+            "初期山札配列メモリダンプ '{}' を読み取れない",
+            cli.path_piles.display()
+        )
+    })?;
+    let piles = piles
+        .lines()
+        .enumerate()
+        .map(|(delay, line)| {
+            CardPile::parse_memory_initial(line)
+                .with_context(|| format!("遅延 {delay} の初期山札配列メモリダンプのパースに失敗"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    ensure!(!piles.is_empty(), "初期山札配列メモリダンプが 1 つもない");
+    let deck = CapturedDeckModel::new(piles);
+
+    let cands = rank_delays(
+        level,
+        cond,
+        cli.money,
+        &deck,
+        deck.delay_max(),
+        cli.beam_width,
+        cli.rng_seed,
+        &cli.frame_model,
+        &evaluator,
+    );
+
+    for cand in cands {
+        match (cand.frame_estimate(), cand.state()) {
+            (Some(frame), Some(state)) => println!(
+                "{}\t{}\t{}\t{}",
+                cand.delay(),
+                frame,
+                state.money(),
+                state.solution()
+            ),
+            _ => println!("{}\t-", cand.delay()),
+        }
+    }
+
+    Ok(())
+}
@@ -6,25 +6,48 @@ use clap::Parser;
 use cadillac_solver::*;
 
 /// 既存の解に対してさらに終盤完全読みを行う。
+///
+/// `--money-required` を省略した場合、所持金は足りるものと仮定して盤面を空にすることのみを条件とする。
 #[derive(Debug, Parser)]
 struct Cli {
     /// ゲームレベル。
-    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(9..=10))]
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
     level: u8,
 
+    /// 開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// レベルクリアに必要な所持金。省略時は所持金は足りるものと仮定する。
+    #[arg(long)]
+    money_required: Option<Money>,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
     /// 初期山札配列メモリダンプのパス。
     path_pile: PathBuf,
 
     /// 既存の解たちが書かれたファイルのパス。
     path_answers: PathBuf,
 
-    /// 終盤完全読み手数。
+    /// 終盤完全読み手数 (`ENDGAME_PLY_COUNT_MAX` 以下)。
     endgame_len: usize,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let level = Level::from_inner(cli.level).unwrap();
+    ensure!(
+        cli.endgame_len <= ENDGAME_PLY_COUNT_MAX,
+        "終盤完全読み手数は {ENDGAME_PLY_COUNT_MAX} 以下でなければならない: {}",
+        cli.endgame_len
+    );
+    let cond = ClearCondition::new(
+        cli.money_required.unwrap_or(0),
+        level.rules().must_clear_board(),
+    );
 
     let pile = std::fs::read_to_string(&cli.path_pile)?;
     let pile = CardPile::parse_memory_initial(pile)?;
@@ -36,45 +59,27 @@ fn main() -> anyhow::Result<()> {
         .collect::<Result<Vec<_>, _>>()?;
 
     for answer in answers {
-        let (state, pile) = answer.endgame_state(level, pile.clone(), cli.endgame_len);
-        optimize(pile, state, answer.frame);
+        let (state, pile) = answer.endgame_state(
+            level,
+            cli.money,
+            pile.clone(),
+            cli.endgame_len,
+            &cli.frame_model,
+        )?;
+        solve_endgame(
+            level,
+            cond,
+            pile,
+            state,
+            answer.frame,
+            &cli.frame_model,
+            &StdoutSink,
+        );
     }
 
     Ok(())
 }
 
-fn optimize(mut pile: CardPile, state_ini: State, mut frame_best: Frame) {
-    dfs(&mut pile, state_ini, &mut frame_best);
-}
-
-fn dfs(pile: &mut CardPile, state: State, frame_best: &mut Frame) {
-    // 枝刈り。
-    if state.frame() >= *frame_best {
-        return;
-    }
-
-    let Some(card) = pile.pop() else {
-        // 所持金は足りるものと仮定する。
-        if state.card_count() == 0 {
-            *frame_best = state.frame();
-            print_answer(&state);
-        }
-        return;
-    };
-
-    let ply = PLY_COUNT_MAX - 1 - pile.len();
-
-    for neighbor in state.neighbors(ply, card) {
-        dfs(pile, neighbor, frame_best);
-    }
-
-    pile.push(card);
-}
-
-fn print_answer(state: &State) {
-    println!("{}\t{}\t{}", state.frame(), state.money(), state.solution());
-}
-
 #[derive(Debug)]
 struct Answer {
     frame: Frame,
@@ -82,15 +87,38 @@ struct Answer {
 }
 
 impl Answer {
-    fn endgame_state(&self, level: Level, pile: CardPile, endgame_len: usize) -> (State, CardPile) {
-        let (mut state, mut pile) = State::new_initial(level, 0, pile);
-        for ply in 0..PLY_COUNT_MAX - endgame_len {
-            let card = pile.pop().unwrap();
-            let col = self.solution.get_move(ply).unwrap();
-            state = state.do_move(ply, card, col);
+    fn endgame_state<M: FrameModel + ?Sized>(
+        &self,
+        level: Level,
+        money: Money,
+        pile: CardPile,
+        endgame_len: usize,
+        model: &M,
+    ) -> anyhow::Result<(State, CardPile)> {
+        let ply_count = level.rules().ply_count() - endgame_len;
+        let solution = self.solution.prefix(ply_count);
+        ensure!(
+            solution.len() == ply_count,
+            "解の手数が足りない: {}",
+            self.solution
+        );
+
+        let records = solution.replay(level, money, pile.clone(), model)?;
+
+        let (mut state, mut pile) = State::new_initial(level, money, pile);
+        if let Some(record) = records.last() {
+            state = State::new(
+                record.frame_elapsed(),
+                record.money(),
+                record.board_after().clone(),
+                solution,
+            );
+        }
+        for _ in 0..ply_count {
+            pile.pop();
         }
 
-        (state, pile)
+        Ok((state, pile))
     }
 }
 
@@ -1,3 +1,4 @@
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 use anyhow::Context as _;
@@ -8,13 +9,17 @@ use cadillac_solver::*;
 #[derive(Debug, Parser)]
 struct Cli {
     /// ゲームレベル。
-    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(9..=10))]
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
     level: u8,
 
     /// 開始時の所持金。
     #[arg(long, default_value_t = 0)]
     money: Money,
 
+    /// レベルクリアに必要な所持金。省略時は原作で確認済みの値を用いる (レベル 9, 10 のみ)。
+    #[arg(long)]
+    money_required: Option<Money>,
+
     /// 既知の最速解のフレーム数。枝刈り用。
     #[arg(long, default_value_t = Frame::MAX)]
     frame_best: Frame,
@@ -23,18 +28,30 @@ struct Cli {
     #[arg(long, default_value_t = 10_000_000)]
     midgame_beam_width: usize,
 
+    /// 探索中に同一盤面について残す状態 ((フレーム数, 所持金) のパレートフロント) の最大個数。省略時は上限なし。
+    #[arg(long)]
+    pareto_len_max: Option<NonZeroUsize>,
+
     /// 上位から何件の状態を終盤完全読みの対象とするか。
     #[arg(long, default_value_t = 1_000)]
     endgame_state_count: usize,
 
     /// 終盤完全読み手数。
-    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(1..=10))]
+    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(1..=12))]
     endgame_len: u8,
 
     /// 評価関数用の乱数シード。
     #[arg(long, default_value_t = 0)]
     rng_seed: u64,
 
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 中盤探索の評価関数のパラメータファイルのパス。省略時はデフォルトのパラメータを用いる。
+    #[arg(long)]
+    evaluator: Option<PathBuf>,
+
     /// 初期山札配列メモリダンプのパス。
     path_pile: PathBuf,
 }
@@ -42,6 +59,17 @@ struct Cli {
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let level = Level::from_inner(cli.level).unwrap();
+    let cond = level
+        .rules()
+        .clear_condition(cli.money_required)
+        .with_context(|| {
+            format!(
+                "レベル {} の必要所持金は未確認なので --money-required で指定すること",
+                cli.level
+            )
+        })?;
+
+    let evaluator = ParamEvaluator::load(cli.evaluator.as_deref())?;
     let endgame_len = usize::from(cli.endgame_len);
 
     let pile = std::fs::read_to_string(&cli.path_pile).with_context(|| {
@@ -61,9 +89,12 @@ fn main() -> anyhow::Result<()> {
         level,
         cli.money,
         pile,
-        PLY_COUNT_MAX - endgame_len,
+        level.rules().ply_count() - endgame_len,
         cli.midgame_beam_width,
+        cli.pareto_len_max.map_or(usize::MAX, NonZeroUsize::get),
         cli.rng_seed,
+        &cli.frame_model,
+        &evaluator,
     );
     eprintln!("cands: {}", cands.len());
     eprintln!("上位候補:");
@@ -75,12 +106,15 @@ fn main() -> anyhow::Result<()> {
 
     cands.truncate(cli.endgame_state_count);
 
-    for (i, cand) in cands.into_iter().enumerate() {
-        if i % 100 == 0 {
-            eprintln!("endgame cand={i}");
-        }
-        solve_endgame(level, pile.clone(), cand, cli.frame_best);
-    }
+    solve_endgame_all(
+        level,
+        cond,
+        &pile,
+        cands,
+        cli.frame_best,
+        &cli.frame_model,
+        &StdoutSink,
+    );
 
     Ok(())
 }
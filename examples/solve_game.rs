@@ -0,0 +1,131 @@
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context as _};
+use clap::Parser;
+
+use cadillac_solver::*;
+
+/// 複数レベルを通しで探索する。各レベルの終了時の所持金は次のレベルに持ち越される。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// 最初のゲームレベル。
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: u8,
+
+    /// 最初のレベル開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// 各レベルのクリアに必要な所持金 (レベル順、カンマ区切り)。
+    /// 省略時は原作で確認済みの値を用いる (レベル 9, 10 のみ)。
+    #[arg(long, value_delimiter = ',')]
+    money_required: Vec<Money>,
+
+    /// 各レベルの探索におけるビーム幅。
+    #[arg(long, default_value_t = 1_000_000)]
+    beam_width: usize,
+
+    /// 探索中に同一盤面について残す状態 ((フレーム数, 所持金) のパレートフロント) の最大個数。省略時は上限なし。
+    #[arg(long)]
+    pareto_len_max: Option<NonZeroUsize>,
+
+    /// 各レベルで上位から何件の状態を終盤完全読みの対象とするか。
+    #[arg(long, default_value_t = 1_000)]
+    endgame_state_count: usize,
+
+    /// 各レベルの終盤完全読み手数。0 ならば中盤探索のみで最後まで探索する。
+    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(0..=12))]
+    endgame_len: u8,
+
+    /// 次のレベルに引き継ぐ解の最大個数。
+    #[arg(long, default_value_t = 10)]
+    route_count_max: usize,
+
+    /// 評価関数用の乱数シード。
+    #[arg(long, default_value_t = 0)]
+    rng_seed: u64,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 中盤探索の評価関数のパラメータファイルのパス。省略時はデフォルトのパラメータを用いる。
+    #[arg(long)]
+    evaluator: Option<PathBuf>,
+
+    /// 各レベルの初期山札配列メモリダンプのパス (レベル順)。
+    #[arg(required = true)]
+    path_piles: Vec<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let level = Level::from_inner(cli.level).unwrap();
+
+    let evaluator = ParamEvaluator::load(cli.evaluator.as_deref())?;
+
+    let piles = cli
+        .path_piles
+        .iter()
+        .map(|path| {
+            let pile = std::fs::read_to_string(path).with_context(|| {
+                format!(
+                    "初期山札配列メモリダンプ '{}' を読み取れない",
+                    path.display()
+                )
+            })?;
+            CardPile::parse_memory_initial(pile).with_context(|| {
+                format!(
+                    "初期山札配列メモリダンプ '{}' のパースに失敗",
+                    path.display()
+                )
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    ensure!(
+        cli.money_required.is_empty() || cli.money_required.len() == piles.len(),
+        "必要所持金の個数が山札の個数と一致しない"
+    );
+    let conds = (0..piles.len())
+        .map(|i| {
+            let level = Level::from_inner(cli.level + i as u8)
+                .with_context(|| format!("レベル {} を超える山札が与えられた", Level::MAX_VALUE))?;
+            level
+                .rules()
+                .clear_condition(cli.money_required.get(i).copied())
+                .with_context(|| {
+                    format!(
+                        "レベル {} の必要所持金は未確認なので --money-required で指定すること",
+                        level.to_inner()
+                    )
+                })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let routes = solve_game(
+        level,
+        cli.money,
+        piles,
+        &conds,
+        &GameSearchConfig {
+            beam_width: cli.beam_width,
+            pareto_len_max: cli.pareto_len_max.map_or(usize::MAX, NonZeroUsize::get),
+            endgame_len: usize::from(cli.endgame_len),
+            endgame_state_count: cli.endgame_state_count,
+            route_count_max: cli.route_count_max,
+            rng_seed: cli.rng_seed,
+        },
+        &cli.frame_model,
+        &evaluator,
+    );
+
+    ensure!(!routes.is_empty(), "クリア可能な解が見つからなかった");
+
+    for route in routes {
+        println!("{route}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use clap::Parser;
+use rand::prelude::*;
+
+use cadillac_solver::*;
+
+/// 中盤探索の評価関数のパラメータを山札コーパス上でランダム探索により調整する。
+///
+/// 現在の最良パラメータを摂動した候補を評価し、最良より良ければ置き換えることを繰り返す。
+/// 最良パラメータは更新のたびに `--output` に書き出されるので、いつ中断してもよい。
+#[derive(Debug, Parser)]
+struct Cli {
+    /// ゲームレベル。
+    #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: u8,
+
+    /// 開始時の所持金。
+    #[arg(long, default_value_t = 0)]
+    money: Money,
+
+    /// レベルクリアに必要な所持金。省略時は原作で確認済みの値を用いる (レベル 9, 10 のみ)。
+    #[arg(long)]
+    money_required: Option<Money>,
+
+    /// 中盤終わりまでの探索におけるビーム幅。
+    #[arg(long, default_value_t = 10_000)]
+    midgame_beam_width: usize,
+
+    /// 上位から何件の状態を終盤完全読みの対象とするか。
+    #[arg(long, default_value_t = 100)]
+    endgame_state_count: usize,
+
+    /// 終盤完全読み手数。
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..=12))]
+    endgame_len: u8,
+
+    /// 評価関数用の乱数シード。
+    #[arg(long, default_value_t = 0)]
+    rng_seed: u64,
+
+    /// パラメータの摂動用の乱数シード。
+    #[arg(long, default_value_t = 0)]
+    tune_seed: u64,
+
+    /// パラメータの摂動の相対幅。
+    #[arg(long, default_value_t = 0.2)]
+    step: f64,
+
+    /// 候補の評価回数。省略時は中断されるまで続ける。
+    #[arg(long)]
+    iteration_count: Option<usize>,
+
+    /// フレームコストのモデルのパラメータ (例: "put_base=37,yaku_base=72")。
+    #[arg(long, default_value_t = LinearFrameModel::default())]
+    frame_model: LinearFrameModel,
+
+    /// 初期の評価関数のパラメータファイルのパス。省略時はデフォルトのパラメータから始める。
+    #[arg(long)]
+    evaluator: Option<PathBuf>,
+
+    /// 最良の評価関数のパラメータの書き出し先。
+    #[arg(long, default_value = "evaluator_best.txt")]
+    output: PathBuf,
+
+    /// 初期山札配列メモリダンプのパスたち (コーパス)。
+    #[arg(required = true)]
+    path_piles: Vec<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let level = Level::from_inner(cli.level).unwrap();
+    let cond = level
+        .rules()
+        .clear_condition(cli.money_required)
+        .with_context(|| {
+            format!(
+                "レベル {} の必要所持金は未確認なので --money-required で指定すること",
+                cli.level
+            )
+        })?;
+
+    let mut evaluator_best = ParamEvaluator::load(cli.evaluator.as_deref())?;
+    let endgame_len = usize::from(cli.endgame_len);
+
+    let piles = cli
+        .path_piles
+        .iter()
+        .map(|path| {
+            let pile = std::fs::read_to_string(path).with_context(|| {
+                format!(
+                    "初期山札配列メモリダンプ '{}' を読み取れない",
+                    path.display()
+                )
+            })?;
+            CardPile::parse_memory_initial(pile).with_context(|| {
+                format!(
+                    "初期山札配列メモリダンプ '{}' のパースに失敗",
+                    path.display()
+                )
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let evaluate = |evaluator: &ParamEvaluator| {
+        evaluate_corpus(
+            level,
+            cond,
+            cli.money,
+            &piles,
+            cli.midgame_beam_width,
+            cli.endgame_state_count,
+            endgame_len,
+            cli.rng_seed,
+            &cli.frame_model,
+            evaluator,
+        )
+    };
+
+    let mut score_best = evaluate(&evaluator_best);
+    println!("initial\t{score_best}");
+    write_evaluator(&cli.output, &evaluator_best)?;
+
+    let mut rng = SmallRng::seed_from_u64(cli.tune_seed);
+
+    for iteration in (0..).take(cli.iteration_count.unwrap_or(usize::MAX)) {
+        let evaluator = perturb_evaluator(&mut rng, &evaluator_best, cli.step);
+        let score = evaluate(&evaluator);
+
+        let improved = score.is_better_than(&score_best);
+        println!(
+            "{iteration}\t{score}\t{}",
+            if improved { "improved" } else { "-" }
+        );
+
+        if improved {
+            evaluator_best = evaluator;
+            score_best = score;
+            write_evaluator(&cli.output, &evaluator_best)?;
+        }
+    }
+
+    println!("best\t{score_best}");
+    print!("{evaluator_best}");
+
+    Ok(())
+}
+
+/// 評価関数のパラメータを `path` に書き出す。中断されても壊れたファイルが残らないよう、一時ファイル経由で置き換える。
+fn write_evaluator(path: &Path, evaluator: &ParamEvaluator) -> anyhow::Result<()> {
+    let path_tmp = path.with_extension("tmp");
+
+    std::fs::write(&path_tmp, evaluator.to_string())
+        .with_context(|| format!("'{}' に書き込めない", path_tmp.display()))?;
+    std::fs::rename(&path_tmp, path)
+        .with_context(|| format!("'{}' に書き込めない", path.display()))?;
+
+    Ok(())
+}
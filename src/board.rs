@@ -2,12 +2,16 @@ use anyhow::{ensure, Context as _};
 
 use crate::card::Card;
 use crate::square::{Col, Row, Square};
-use crate::Frame;
 
 /// 盤面。
-#[repr(transparent)]
+///
+/// 各マスのカードに加え、盤面の Zobrist ハッシュ値を差分更新で保持している。
+/// 盤面の変更は必ずハッシュ値を更新するメソッドを経由する。
 #[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
-pub struct Board([Option<Card>; Col::NUM * Row::NUM]);
+pub struct Board {
+    cells: [Option<Card>; Col::NUM * Row::NUM],
+    hash: u64,
+}
 
 impl Board {
     pub fn new() -> Self {
@@ -15,7 +19,12 @@ impl Board {
     }
 
     pub fn card_count(&self) -> usize {
-        self.0.iter().flatten().count()
+        self.cells.iter().flatten().count()
+    }
+
+    /// 盤面上の全てのカードを列挙する。
+    pub fn cards(&self) -> impl Iterator<Item = Card> + '_ {
+        self.cells.iter().copied().flatten()
     }
 
     pub fn col(&self, col: Col) -> [Option<Card>; 5] {
@@ -23,63 +32,87 @@ impl Board {
     }
 
     pub fn row(&self, row: Row) -> [Option<Card>; 5] {
-        std::array::from_fn(|col| self.0[5 * col + row.to_index()])
+        std::array::from_fn(|col| self.cells[5 * col + row.to_index()])
     }
 
-    fn col_ref(&self, col: Col) -> &[Option<Card>; 5] {
-        unsafe {
-            self.0[5 * col.to_index()..][..5]
-                .try_into()
-                .unwrap_unchecked()
-        }
+    /// 指定した列に積まれているカードの枚数を返す。全てのカードは落下済みと仮定している。
+    pub fn col_height(&self, col: Col) -> usize {
+        self.col_ref(col)
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(Row::NUM)
     }
 
-    fn col_mut(&mut self, col: Col) -> &mut [Option<Card>; 5] {
+    fn col_ref(&self, col: Col) -> &[Option<Card>; 5] {
         unsafe {
-            (&mut self.0[5 * col.to_index()..][..5])
+            self.cells[5 * col.to_index()..][..5]
                 .try_into()
                 .unwrap_unchecked()
         }
     }
 
     /// 指定した列に指定したカードを落下させる。役判定/処理までは行わない。
-    /// (結果の盤面, フレームコスト) を返す。
-    pub fn put(&self, col: Col, card: Card) -> Option<(Self, Frame)> {
+    /// (結果の盤面, カードが着地した行) を返す。
+    pub fn put(&self, col: Col, card: Card) -> Option<(Self, Row)> {
         let i = self.col_ref(col).iter().position(Option::is_none)?;
 
         let mut after = self.clone();
-        after.col_mut(col)[i] = Some(card);
+        after.set_cell(5 * col.to_index() + i, Some(card));
 
-        let frame = 37 + 16 * (4 - i as Frame);
+        let row = unsafe { Row::from_inner_unchecked(Row::MIN_VALUE + i as u8) };
 
-        Some((after, frame))
+        Some((after, row))
+    }
+
+    /// 盤面を 1 マス 6bit に詰めたものを返す。
+    pub fn pack(&self) -> PackedBoard {
+        let mut bytes = [0; PackedBoard::GROUP_COUNT * 3];
+        for (g, cells) in self.cells.chunks(4).enumerate() {
+            let bits = cells.iter().enumerate().fold(0_u32, |acc, (k, card)| {
+                acc | u32::from(card.map_or(0, Card::to_cadillac_value)) << (6 * k)
+            });
+            bytes[3 * g..][..3].copy_from_slice(&bits.to_le_bytes()[..3]);
+        }
+
+        PackedBoard(bytes[..PackedBoard::BYTE_LEN].try_into().unwrap())
     }
 
     /// 空中にある全てのカードを落下完了させる。in-place 処理。
-    /// フレームコストを返す。
-    pub fn fall(&mut self) -> Frame {
-        // 1 マスの落下に 8F かかるとする(概算)。
-        fn fall_col(ary: &mut [Option<Card>; 5]) -> Frame {
-            let mut frame = 0;
+    /// 全てのカードの落下マス数の合計を返す。
+    pub fn fall(&mut self) -> usize {
+        let mut dist = 0;
+
+        for col in Col::all() {
+            let base = 5 * col.to_index();
             let mut i = 0;
             for j in 0..5 {
-                if let Some(card) = ary[j].take() {
-                    ary[i] = Some(card);
-                    frame += 8 * (j - i) as Frame;
+                if let Some(card) = self.cells[base + j] {
+                    if i != j {
+                        self.set_cell(base + j, None);
+                        self.set_cell(base + i, Some(card));
+                        dist += j - i;
+                    }
                     i += 1;
                 }
             }
-            frame
         }
 
-        let mut frame = 0;
+        dist
+    }
 
-        for col in Col::all() {
-            let ary = self.col_mut(col);
-            frame += fall_col(ary);
-        }
+    /// マス `sq` のカードを `card` にする。
+    pub fn set(&mut self, sq: Square, card: Option<Card>) {
+        self.set_cell(sq.to_index(), card);
+    }
+
+    /// 盤面の Zobrist ハッシュ値を返す。空の盤面では 0。
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
 
-        frame
+    fn set_cell(&mut self, idx: usize, card: Option<Card>) {
+        let old = std::mem::replace(&mut self.cells[idx], card);
+        self.hash ^= zobrist_key(idx, old) ^ zobrist_key(idx, card);
     }
 
     fn parse(s: &str) -> anyhow::Result<Self> {
@@ -92,7 +125,7 @@ impl Board {
             let ary = Self::parse_row(line, row)?;
             for col in Col::all() {
                 let sq = Square::new(col, row);
-                board[sq] = ary[col.to_index()];
+                board.set(sq, ary[col.to_index()]);
             }
         }
 
@@ -129,16 +162,45 @@ impl std::ops::Index<Square> for Board {
     type Output = Option<Card>;
 
     fn index(&self, sq: Square) -> &Self::Output {
-        unsafe { self.0.get_unchecked(sq.to_index()) }
+        unsafe { self.cells.get_unchecked(sq.to_index()) }
     }
 }
 
-impl std::ops::IndexMut<Square> for Board {
-    fn index_mut(&mut self, sq: Square) -> &mut Self::Output {
-        unsafe { self.0.get_unchecked_mut(sq.to_index()) }
+/// 保持している Zobrist ハッシュ値のみを用いる (等しい盤面の Zobrist ハッシュ値は等しい)。
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
     }
 }
 
+/// Zobrist ハッシュの乱数表。`ZOBRIST_TABLE[i][v]` は `i` 番目のマスに値 `v` ([`Card::to_cadillac_value`]) のカードがあることに対応する。
+static ZOBRIST_TABLE: [[u64; 1 << 6]; Col::NUM * Row::NUM] = {
+    // splitmix64 で生成する。
+    let mut table = [[0; 1 << 6]; Col::NUM * Row::NUM];
+    let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut i = 0;
+    while i < table.len() {
+        let mut v = 0;
+        while v < table[i].len() {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            table[i][v] = z ^ (z >> 31);
+            v += 1;
+        }
+        i += 1;
+    }
+    table
+};
+
+/// `idx` 番目のマスの内容 `card` に対応する Zobrist ハッシュの値を返す。空マスは 0。
+fn zobrist_key(idx: usize, card: Option<Card>) -> u64 {
+    card.map_or(0, |card| {
+        ZOBRIST_TABLE[idx][usize::from(card.to_cadillac_value())]
+    })
+}
+
 impl std::str::FromStr for Board {
     type Err = anyhow::Error;
 
@@ -163,6 +225,38 @@ impl std::fmt::Display for Board {
     }
 }
 
+/// 1 マス 6bit に詰めた盤面。探索で大量の盤面を保持する際のメモリ節約用。
+///
+/// 空マスを 0、カードを [`Card::to_cadillac_value`] で表す。25 マスで 150 bit (19 バイト) となる。
+/// 大小関係は [`Board`] のものとは一致しない。
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PackedBoard([u8; PackedBoard::BYTE_LEN]);
+
+impl PackedBoard {
+    const BYTE_LEN: usize = (6 * Col::NUM * Row::NUM).div_ceil(8);
+
+    /// 4 マス (3 バイト) ずつ区切ったときの区切りの個数。
+    const GROUP_COUNT: usize = (Col::NUM * Row::NUM).div_ceil(4);
+
+    /// 元の盤面を返す。
+    pub fn unpack(&self) -> Board {
+        let mut bytes = [0; Self::GROUP_COUNT * 3];
+        bytes[..Self::BYTE_LEN].copy_from_slice(&self.0);
+
+        let mut cells = [None; Col::NUM * Row::NUM];
+        let mut hash = 0;
+        for (g, group) in cells.chunks_mut(4).enumerate() {
+            let bits = u32::from_le_bytes([bytes[3 * g], bytes[3 * g + 1], bytes[3 * g + 2], 0]);
+            for (k, cell) in group.iter_mut().enumerate() {
+                *cell = Card::from_cadillac_value(((bits >> (6 * k)) & 0x3F) as u8);
+                hash ^= zobrist_key(4 * g + k, *cell);
+            }
+        }
+
+        Board { cells, hash }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use indoc::indoc;
@@ -176,6 +270,53 @@ mod tests {
         s.as_ref().parse().unwrap()
     }
 
+    #[test]
+    fn test_packed_board() {
+        let board = parse_board(indoc! {"
+            ....SA....
+            S2..C9..HT
+            CJCQS5DKDA
+            D2D5HAH4C3
+            S3CAH3D6D7
+        "});
+
+        assert_eq!(board.pack().unpack(), board);
+        assert_eq!(Board::new().pack().unpack(), Board::new());
+        assert_ne!(board.pack(), Board::new().pack());
+        assert_eq!(std::mem::size_of::<PackedBoard>(), 19);
+    }
+
+    #[test]
+    fn test_board_zobrist_hash() {
+        assert_eq!(Board::new().zobrist_hash(), 0);
+
+        let mut board = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            S2........
+            S3C4......
+        "});
+        assert_ne!(board.zobrist_hash(), 0);
+        assert_eq!(board.pack().unpack().zobrist_hash(), board.zobrist_hash());
+
+        // 差分更新したハッシュ値は盤面のみで決まる。
+        board.set(SQ_A1, None);
+        board.fall();
+        let expected = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2C4......
+        "});
+        assert_eq!(board.zobrist_hash(), expected.zobrist_hash());
+
+        board.set(SQ_B1, None);
+        board.set(SQ_A1, None);
+        assert_eq!(board.zobrist_hash(), 0);
+    }
+
     #[test]
     fn test_board_io() {
         assert_eq!(parse_board(Board::new().to_string()), Board::new());
@@ -290,7 +431,7 @@ mod tests {
 
         let mut board = parse_board(case.0);
         let after = parse_board(case.1);
-        board.fall();
+        assert_eq!(board.fall(), 14);
         assert_eq!(board, after);
     }
 }
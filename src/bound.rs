@@ -0,0 +1,250 @@
+//! 探索の枝刈りに用いる見積もり。
+
+use arrayvec::ArrayVec;
+
+use crate::card::Card;
+use crate::frame_model::FrameModel;
+use crate::level::ClearCondition;
+use crate::position::CardPile;
+use crate::square::{Col, Row, Square};
+use crate::state::State;
+use crate::yaku::{calc_prize_upper_bound, CardSet};
+use crate::{Frame, Money};
+
+/// 1 回の連鎖の段数の上限。各段で 3 枚以上のカードが消えるので、盤面のマス数から決まる。
+const CHAIN_LEN_MAX: usize = Square::NUM / 3;
+
+/// 残りの手でかかるフレーム数の下界 (真の値を超えない見積もり)。
+///
+/// 以下の事実のみを用いる:
+///
+/// * カードが着地する行は着手時点での列の高さで決まり、列の高さは 1 手につき高々 1 しか増えない。
+///   よって山札の `i` 枚目 (0-based) のカードは、現在の最も高い列の高さに `i` を足した行より上には着地しない。
+/// * 盤面を空にする必要があるレベルでは、盤面と山札の全てのカードがいずれかの役で消去される。
+///   役 1 回で消えるカードは 3 枚以上 `Square::NUM` 枚以下で、その演出にはそれに応じた `yaku` のフレーム数がかかる。
+///
+/// 落下のフレーム数は 0 と見積もる。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FrameLowerBound {
+    /// `put_sum_min[h][n]`: 最も高い列の高さが `h` のとき、`n` 枚のカードを置くのにかかるフレーム数の下界。
+    put_sum_min: [[Frame; Card::NUM + 1]; Row::NUM],
+    /// `erase_min[n]`: `n` 枚のカードを全て消去するのにかかるフレーム数の下界。不可能ならば `Frame::MAX`。
+    erase_min: [Frame; Card::NUM + Square::NUM + 1],
+}
+
+impl FrameLowerBound {
+    /// フレームコストのモデル `model` に対する下界を作る。
+    pub fn new<M: FrameModel + ?Sized>(model: &M) -> Self {
+        // put_min[r]: 下から r 行目 (0-based) 以下に置くフレーム数の最小値。
+        let mut put_min = [Frame::MAX; Row::NUM];
+        for (r, row) in Row::all().into_iter().enumerate() {
+            put_min[r] = model
+                .put(row)
+                .min(if r == 0 { Frame::MAX } else { put_min[r - 1] });
+        }
+
+        let put_sum_min: [[Frame; Card::NUM + 1]; Row::NUM] = std::array::from_fn(|h| {
+            let mut sums = [0 as Frame; Card::NUM + 1];
+            for n in 1..=Card::NUM {
+                let r = (h + n - 1).min(Row::NUM - 1);
+                sums[n] = sums[n - 1].saturating_add(put_min[r]);
+            }
+            sums
+        });
+
+        // yaku_min[k]: k 枚を一度に消去するフレーム数の (連鎖段数についての) 最小値。
+        let yaku_min: [Frame; Square::NUM + 1] = std::array::from_fn(|k| {
+            (0..CHAIN_LEN_MAX)
+                .map(|chain| model.yaku(chain, k))
+                .min()
+                .unwrap()
+        });
+
+        let mut erase_min = [Frame::MAX; Card::NUM + Square::NUM + 1];
+        erase_min[0] = 0;
+        for n in 3..erase_min.len() {
+            erase_min[n] = (3..=n.min(Square::NUM))
+                .map(|k| erase_min[n - k].saturating_add(yaku_min[k]))
+                .min()
+                .unwrap();
+        }
+
+        Self {
+            put_sum_min,
+            erase_min,
+        }
+    }
+
+    /// クリア条件 `cond` の下で、状態 `state` から山札 `pile` を全て置き終えるまでにかかるフレーム数の下界を返す。
+    ///
+    /// クリア条件を満たしえないことが判明した場合は `Frame::MAX` を返す。
+    pub fn eval(&self, cond: ClearCondition, state: &State, pile: &CardPile) -> Frame {
+        let height = Col::all()
+            .into_iter()
+            .map(|col| state.board().col_height(col))
+            .max()
+            .unwrap()
+            .min(Row::NUM - 1);
+        let frame_put = self.put_sum_min[height][pile.len()];
+
+        let frame_erase = if cond.must_clear_board() {
+            self.erase_min[usize::from(state.card_count()) + pile.len()]
+        } else {
+            0
+        };
+
+        frame_put.saturating_add(frame_erase)
+    }
+}
+
+/// 状態 `state` から山札 `pile` を全て置き終えるまでに得られる賞金の上界を返す。
+///
+/// 盤面と山札に残っているカードのみから、成立しうる役の種類と賞金の倍率表を考慮して見積もる。
+pub fn prize_upper_bound(state: &State, pile: &CardPile) -> Money {
+    calc_prize_upper_bound(&remaining_cards(state, pile))
+}
+
+/// 状態 `state` から山札 `pile` を全て置き終えた時点で盤面を空にできる可能性があるかどうかを返す。
+///
+/// 偽ならば決して盤面を空にできない。真であっても空にできるとは限らない。
+/// 盤面と山札の全てのカードはいずれ 3 枚以上の役で消去されねばならないので、以下の場合に偽となる:
+///
+/// * 残りのカードが 1 枚または 2 枚しかない。
+/// * 残りのカードだけでは、いずれの役にも含まれえないカードがある。
+pub fn can_clear_board(state: &State, pile: &CardPile) -> bool {
+    if matches!(usize::from(state.card_count()) + pile.len(), 1..=2) {
+        return false;
+    }
+
+    let mut set = CardSet::default();
+    for card in state.board().cards().chain(pile.iter()) {
+        set.insert(card);
+    }
+
+    state
+        .board()
+        .cards()
+        .chain(pile.iter())
+        .all(|card| set.can_form_hand(card))
+}
+
+/// 盤面と山札に残っている全てのカードを返す。探索の各ノードで呼ばれるので、ヒープ確保を避けて固定長の配列に集める。
+fn remaining_cards(state: &State, pile: &CardPile) -> ArrayVec<Card, { Card::NUM }> {
+    state.board().cards().chain(pile.iter()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::card::*;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+    use crate::solution::Solution;
+
+    use super::*;
+
+    fn make_state(board: &str) -> State {
+        State::new(0, 0, board.parse().unwrap(), Solution::new())
+    }
+
+    /// 長さ `len` の山札を作る。
+    fn make_pile(len: usize) -> CardPile {
+        CardPile::from_cards(&Card::all()[..len])
+    }
+
+    #[test]
+    fn test_frame_lower_bound() {
+        let bound = FrameLowerBound::new(&LinearFrameModel::DEFAULT);
+        let cond = LEVEL_9.rules().clear_condition(None).unwrap();
+
+        let empty = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+        "});
+        let two = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2S3......
+        "});
+
+        assert_eq!(bound.eval(cond, &empty, &make_pile(0)), 0);
+        assert_eq!(bound.eval(cond, &two, &make_pile(0)), Frame::MAX);
+        // 高さ 1, 2, 3 の行に 3 枚置いて 1 回の役で 5 枚消去する。
+        assert_eq!(
+            bound.eval(cond, &two, &make_pile(3)),
+            (37 + 3 * 16) + (37 + 2 * 16) + (37 + 16) + 72 + 5 * 8
+        );
+        // 高さ 1 の行に 1 枚置いて 1 回の役で 3 枚消去する。
+        assert_eq!(
+            bound.eval(cond, &two, &make_pile(1)),
+            (37 + 3 * 16) + 72 + 3 * 8
+        );
+    }
+
+    #[test]
+    fn test_prize_upper_bound() {
+        let empty = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+        "});
+        let royal = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            SASKSQ....
+        "});
+
+        // どの役も成立しえない。
+        let pile = CardPile::from_cards(&[CARD_S2, CARD_C5, CARD_H9]);
+        assert_eq!(prize_upper_bound(&empty, &pile), 0);
+
+        // ロイヤルフラッシュ (+ ストレートフラッシュ + ストレート + フラッシュ) が成立しうる。
+        // 各カードの 1 ラインあたりの按分は 120/5 + 200/5 + 50/5 + 30/5 = 80 で、行と列の 2 ライン分を数える。
+        let pile = CardPile::from_cards(&[CARD_SJ, CARD_ST]);
+        assert_eq!(prize_upper_bound(&royal, &pile), 5 * 2 * 80);
+
+        // スリーカードのみが成立しうる。
+        // 各カードの 1 ラインあたりの按分は 30/3 = 10 で、行と列の 2 ライン分を数える。
+        let pile = CardPile::from_cards(&[CARD_S7, CARD_C7, CARD_H7]);
+        assert_eq!(prize_upper_bound(&empty, &pile), 3 * 2 * 10);
+    }
+
+    #[test]
+    fn test_can_clear_board() {
+        let empty = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+        "});
+        let two = make_state(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2S3......
+        "});
+
+        assert!(can_clear_board(&empty, &CardPile::from_cards(&[])));
+        assert!(!can_clear_board(&two, &CardPile::from_cards(&[])));
+        assert!(can_clear_board(&two, &CardPile::from_cards(&[CARD_S4])));
+        assert!(can_clear_board(&two, &CardPile::from_cards(&[CARD_C4])));
+        assert!(can_clear_board(&two, &CardPile::from_cards(&[CARD_SK])));
+        // HK は役に含まれえない。
+        assert!(!can_clear_board(
+            &two,
+            &CardPile::from_cards(&[CARD_S4, CARD_HK])
+        ));
+    }
+}
@@ -1,57 +1,683 @@
 //! 終盤の完全読み。
 
-use crate::level::{Level, LEVEL_10, LEVEL_9};
+use std::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use arrayvec::ArrayVec;
+use rayon::prelude::*;
+
+use crate::board::PackedBoard;
+use crate::bound::{can_clear_board, prize_upper_bound, FrameLowerBound};
+use crate::frame_model::FrameModel;
+use crate::level::{ClearCondition, Level};
 use crate::position::CardPile;
 use crate::state::State;
-use crate::{Frame, PLY_COUNT_MAX};
+use crate::{Frame, Money};
+
+/// 完全読み手数(山札残り枚数)の最大値。
+///
+/// 実際のクリア条件 (必要所持金と盤面を空にすること) の下で、中盤探索の上位 20 状態から完全読みした計測値
+/// (`test_solve_endgame_long` およびランダムな山札 2 つ, レベル 9, 10, 1 コア) は以下の通り:
+/// 10 手で 10〜15 秒、12 手で 5〜21 秒、14 手で 39〜87 秒。
+/// 14 手以上は 10 手より明らかに遅く常用できないので、12 手を上限とする。多コアでの計測は行っていない。
+pub const ENDGAME_PLY_COUNT_MAX: usize = 12;
+
+/// `solve_endgame` で根から展開して並列に探索する手数。
+const ROOT_SPLIT_PLY_COUNT: usize = 2;
+
+/// 反復深化で最初に下界へ加える、最速解のフレーム数の上限の幅。反復ごとに倍にする。
+const FRAME_LIMIT_STEP_INITIAL: Frame = 256;
+
+/// 置換表を用いる山札残り枚数の下限。
+const TRANS_TABLE_PILE_LEN_MIN: usize = 3;
+
+/// 完全読みで見つかった解の受け取り先。
+///
+/// 解はそれまでの最速解を真に改善するたびに通知されるので、通知される解のフレーム数は狭義単調減少する。
+/// 通知は探索器内で直列化されるが、呼び出し元のスレッドは不定なので `Sync` を要求する。
+pub trait EndgameSink: Sync {
+    /// 解 (クリア条件を満たす最終状態) `state` が見つかったときに呼ばれる。
+    fn on_answer(&self, state: &State);
+
+    /// 並列探索の根となる `total` 個の状態のうち `done` 個の探索を終えるたびに呼ばれる。
+    /// 反復深化の反復ごとに `done` は 1 から数え直す。この通知は直列化されない。デフォルトでは何もしない。
+    fn on_progress(&self, done: usize, total: usize) {
+        let _ = (done, total);
+    }
+}
+
+impl<F: Fn(&State) + Sync> EndgameSink for F {
+    fn on_answer(&self, state: &State) {
+        self(state)
+    }
+}
+
+/// 解を "frame\tmoney\tsolution" 形式で標準出力に書き出す。探索の進捗は 100 状態ごとに標準エラー出力に書き出す。
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl EndgameSink for StdoutSink {
+    fn on_answer(&self, state: &State) {
+        println!("{}\t{}\t{}", state.frame(), state.money(), state.solution());
+    }
+
+    fn on_progress(&self, done: usize, total: usize) {
+        if done.is_multiple_of(100) {
+            eprintln!("endgame done={done}/{total}");
+        }
+    }
+}
+
+/// 解を全て集める。
+#[derive(Debug, Default)]
+pub struct CollectSink(Mutex<Vec<State>>);
+
+impl CollectSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 集めた解たちを見つかった順に返す。
+    pub fn into_answers(self) -> Vec<State> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+impl EndgameSink for CollectSink {
+    fn on_answer(&self, state: &State) {
+        self.0.lock().unwrap().push(state.clone());
+    }
+}
+
+/// 完全読みを行い、クリア条件 `cond` を満たす解を `sink` に通知する。フレームコストは `model` に従う。
+///
+/// 根から数手展開した状態たちを並列に探索する。
+/// 最終的な最速解のフレーム数 (解が見つからなければ `frame_best`) を返す。
+pub fn solve_endgame<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    mut pile: CardPile,
+    state_ini: State,
+    frame_best: Frame,
+    model: &M,
+    sink: &dyn EndgameSink,
+) -> Frame {
+    assert!(
+        pile.len() <= ENDGAME_PLY_COUNT_MAX,
+        "完全読みは {ENDGAME_PLY_COUNT_MAX} 手が上限"
+    );
 
-/// 完全読み手数(山札残り枚数)の最大値。とりあえず 10 手読みを上限とする (`5^10 ~ 10^7`)。
-pub const ENDGAME_PLY_COUNT_MAX: usize = 10;
+    // 根から数手展開し、それらを並列に探索する。
+    let mut states = vec![state_ini];
+    for _ in 0..ROOT_SPLIT_PLY_COUNT.min(pile.len()) {
+        let card = pile.pop().unwrap();
+        let ply = level.rules().ply_count() - 1 - pile.len();
+        states = states
+            .iter()
+            .flat_map(|state| state.neighbors(ply, card, model))
+            .collect();
+    }
 
-/// 完全読みを行い、解集合を出力する。
-pub fn solve_endgame(level: Level, mut pile: CardPile, state_ini: State, mut frame_best: Frame) {
-    assert!(level >= LEVEL_9, "レベル 8 以下は未サポート");
+    search(level, cond, &pile, states, frame_best, model, sink)
+}
+
+/// 複数の候補状態について並列に完全読みを行い、クリア条件 `cond` を満たす解を `sink` に通知する。フレームコストは `model` に従う。
+///
+/// 全候補で最速解のフレーム数および置換表を共有するため、ある候補の探索結果は他の候補の枝刈りにも使われる。
+/// 最終的な最速解のフレーム数 (解が見つからなければ `frame_best`) を返す。
+pub fn solve_endgame_all<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    pile: &CardPile,
+    states: Vec<State>,
+    frame_best: Frame,
+    model: &M,
+    sink: &dyn EndgameSink,
+) -> Frame {
     assert!(
         pile.len() <= ENDGAME_PLY_COUNT_MAX,
         "完全読みは {ENDGAME_PLY_COUNT_MAX} 手が上限"
     );
 
-    dfs(level, &mut pile, state_ini, &mut frame_best);
+    search(level, cond, pile, states, frame_best, model, sink)
 }
 
-fn dfs(level: Level, pile: &mut CardPile, state: State, frame_best: &mut Frame) {
-    // 枝刈り。
-    if state.frame() >= *frame_best {
-        return;
+/// `states` の各状態から山札 `pile` を置き終えるまでの完全読みを並列に行い、最速解のフレーム数を返す。
+///
+/// 最速解のフレーム数の上限を根の下界から緩めながら探索を繰り返す (反復深化)。
+/// 上限未満の解が見つかればそれが最速解なので、その時点で打ち切る。
+/// 上限により枝刈りされた状態がなければ、上限を緩めても解は見つからないので打ち切る。
+/// 上限が厳しいうちは枝刈りが強く効くので、最速解が根の下界に近ければ少ない反復で済む。
+/// 置換表の領域は全反復で使い回すが、記録は反復ごとに無効にする
+/// (上限で打ち切られた部分木の記録は、上限を緩めた後の枝刈りには使えないため)。
+fn search<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    pile: &CardPile,
+    states: Vec<State>,
+    frame_best: Frame,
+    model: &M,
+    sink: &dyn EndgameSink,
+) -> Frame {
+    let mut frame_limit = Frame::MIN;
+    let mut step = FRAME_LIMIT_STEP_INITIAL;
+    let mut frame_cut_min = Frame::MIN;
+    let mut table = TransTable::new();
+    loop {
+        // 次の上限は、前回枝刈りされた状態の下界の最小値を超え、かつ前回の上限から `step` 以上緩めたものとする。
+        frame_limit = frame_limit
+            .saturating_add(step)
+            .max(frame_cut_min.saturating_add(1))
+            .min(frame_best);
+
+        table.clear();
+        let searcher = Searcher::new(level, cond, frame_limit, &table, model, sink);
+        let done_count = AtomicUsize::new(0);
+
+        states.par_iter().for_each(|state| {
+            searcher.dfs(&mut pile.clone(), state.clone());
+
+            let done_count = done_count.fetch_add(1, Ordering::Relaxed) + 1;
+            sink.on_progress(done_count, states.len());
+        });
+
+        let frame = searcher.frame_best.into_inner();
+        frame_cut_min = searcher.frame_cut_min.into_inner();
+        if frame < frame_limit {
+            return frame;
+        }
+        if frame_limit == frame_best || frame_cut_min >= frame_best {
+            return frame_best;
+        }
+        step = step.saturating_mul(2);
     }
+}
+
+/// 完全読みの探索器。並列探索の各スレッドから共有される。
+struct Searcher<'a, M: ?Sized> {
+    level: Level,
+    cond: ClearCondition,
+    frame_best: AtomicU16,
+    /// フレーム数の下界により枝刈りした状態たちの下界の最小値。
+    frame_cut_min: AtomicU16,
+    /// 最速解の更新と `sink` への通知を不可分に行うためのロック。
+    answer_lock: Mutex<()>,
+    table: &'a TransTable,
+    frame_bound: FrameLowerBound,
+    model: &'a M,
+    sink: &'a dyn EndgameSink,
+}
 
-    let Some(card) = pile.pop() else {
-        if state_is_ok(level, &state) {
-            *frame_best = state.frame();
-            print_answer(&state);
+impl<'a, M: FrameModel + ?Sized> Searcher<'a, M> {
+    fn new(
+        level: Level,
+        cond: ClearCondition,
+        frame_best: Frame,
+        table: &'a TransTable,
+        model: &'a M,
+        sink: &'a dyn EndgameSink,
+    ) -> Self {
+        Self {
+            level,
+            cond,
+            frame_best: AtomicU16::new(frame_best),
+            frame_cut_min: AtomicU16::new(Frame::MAX),
+            answer_lock: Mutex::new(()),
+            table,
+            frame_bound: FrameLowerBound::new(model),
+            model,
+            sink,
         }
-        return;
-    };
+    }
+
+    /// 残りの手でかかるフレーム数の下界を加えた、最終的なフレーム数の下界を返す。
+    fn frame_min(&self, state: &State, pile: &CardPile) -> Frame {
+        state
+            .frame()
+            .saturating_add(self.frame_bound.eval(self.cond, state, pile))
+    }
 
-    let ply = PLY_COUNT_MAX - 1 - pile.len();
+    fn dfs(&self, pile: &mut CardPile, state: State) {
+        // 枝刈り。残りの手でかかるフレーム数の下界を加えても最速解に届かなければ打ち切る。
+        let frame_min = self.frame_min(&state, pile);
+        if frame_min >= self.frame_best.load(Ordering::Relaxed) {
+            self.on_cut(frame_min);
+            return;
+        }
+
+        // 残りの山札で得られる賞金を最大限見積もっても必要所持金に届かなければ打ち切る。
+        let money_required = self.cond.money_required();
+        if state.money() < money_required
+            && state
+                .money()
+                .saturating_add(prize_upper_bound(&state, pile))
+                < money_required
+        {
+            return;
+        }
+
+        // 盤面を空にできる見込みがなければ打ち切る。
+        if self.cond.must_clear_board() && !can_clear_board(&state, pile) {
+            return;
+        }
+
+        // 手順前後などで同一局面に (フレーム数, 所持金) の両方で劣らない状態が既に到達していれば、
+        // そちらの探索で同等以上の解が見つかるので打ち切る。
+        // 末端付近では部分木が小さく、置換表を引くコストの方が大きいので使わない。
+        if pile.len() >= TRANS_TABLE_PILE_LEN_MIN && !self.table.try_insert(&state, pile.len()) {
+            return;
+        }
+
+        let Some(card) = pile.pop() else {
+            if self.cond.is_cleared(state.money(), state.board()) {
+                self.on_answer(&state);
+            }
+            return;
+        };
+
+        let ply = self.level.rules().ply_count() - 1 - pile.len();
+
+        // 見込みの良い手から順に調べ、良い解を早く見つけて枝刈りを効かせる。
+        let mut neighbors: ArrayVec<(Frame, State), 5> = state
+            .neighbors(ply, card, self.model)
+            .into_iter()
+            .map(|neighbor| (self.frame_min(&neighbor, pile), neighbor))
+            .collect();
+        neighbors.sort_unstable_by_key(|&(frame_min, _)| frame_min);
+
+        for (frame_min, neighbor) in neighbors {
+            // 以降の手は全て下界が最速解以上。
+            if frame_min >= self.frame_best.load(Ordering::Relaxed) {
+                self.on_cut(frame_min);
+                break;
+            }
+            self.dfs(pile, neighbor);
+        }
+
+        pile.push(card);
+    }
+
+    /// フレーム数の下界 `frame_min` が最速解 (または反復深化の上限) に届かず枝刈りしたことを記録する。
+    fn on_cut(&self, frame_min: Frame) {
+        // ほとんどの場合は記録済みの値以上なので、先に読み出しのみで判定して書き込みの競合を避ける。
+        if frame_min < self.frame_cut_min.load(Ordering::Relaxed) {
+            self.frame_cut_min.fetch_min(frame_min, Ordering::Relaxed);
+        }
+    }
 
-    for neighbor in state.neighbors(ply, card) {
-        dfs(level, pile, neighbor, frame_best);
+    /// 解 `state` が最速解を真に改善していれば最速解を更新し、`sink` に通知する。
+    fn on_answer(&self, state: &State) {
+        // 他のスレッドが先により良い解を見つけている可能性がある。
+        // 比較と更新と通知をロック内で行うことで、通知される解のフレーム数が単調減少することを保証する。
+        // 解が見つかる頻度は低いので、ロックのコストは問題にならない。
+        let _guard = self.answer_lock.lock().unwrap();
+        if state.frame() < self.frame_best.load(Ordering::Relaxed) {
+            self.frame_best.store(state.frame(), Ordering::Relaxed);
+            self.sink.on_answer(state);
+        }
     }
+}
 
-    pile.push(card);
+/// 終盤探索用の置換表。
+///
+/// 局面 (盤面, 山札残り枚数) ごとに、そこへ到達した状態の (フレーム数, 所持金) のうち
+/// パレート最適なものたちを記録する。
+/// 以降の展開は局面のみで決まるので、記録済みのものに両方で劣る状態は探索不要となる。
+///
+/// メモリ使用量を抑えるため、盤面の Zobrist ハッシュ値で定まる固定個数のスロットに記録する。
+/// スロットが別の局面で埋まっている場合、山札残り枚数が同じか多い (部分木が大きい) 局面で置き換える。
+/// 記録が失われても枝刈りが弱まるだけで、探索結果は変わらない。
+///
+/// 並列探索から共有されるため、ロック競合を抑えるよう Zobrist ハッシュ値でシャードに分割している。
+struct TransTable {
+    shards: Vec<Mutex<Vec<TransTableEntry>>>,
+    /// 現在の世代。世代の異なる記録は未使用とみなす。
+    generation: u8,
 }
 
-fn state_is_ok(level: Level, state: &State) -> bool {
-    let money_min = match level {
-        LEVEL_9 => 200,
-        LEVEL_10 => 250,
-        _ => unreachable!(),
-    };
+/// 置換表の 1 スロット。世代が置換表のものと異なるか、`front` が空ならば未使用。
+#[derive(Clone, Debug, Default)]
+struct TransTableEntry {
+    generation: u8,
+    board: PackedBoard,
+    pile_len: u8,
+    front: ArrayVec<(Frame, Money), { TransTable::FRONT_LEN_MAX }>,
+}
+
+impl TransTable {
+    const SHARD_COUNT: usize = 64;
+
+    /// シャードあたりのスロット数。全体で約 80 MB となる。
+    const SHARD_SLOT_COUNT: usize = 1 << 15;
+
+    /// 1 局面について記録する (フレーム数, 所持金) の最大個数。
+    const FRONT_LEN_MAX: usize = 4;
+
+    fn new() -> Self {
+        // スロットは短い探索で無駄に確保しないよう、各シャードの初回アクセス時に確保する。
+        Self {
+            shards: (0..Self::SHARD_COUNT)
+                .map(|_| Mutex::new(Vec::new()))
+                .collect(),
+            generation: 0,
+        }
+    }
 
-    state.money() >= money_min && state.card_count() == 0
+    /// 全ての記録を無効にする。確保済みのスロットはそのまま使い回す。
+    fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        // 世代が一周したら、古い記録が現在の世代とみなされないよう実際に消去する。
+        if self.generation == 0 {
+            for shard in &mut self.shards {
+                shard.get_mut().unwrap().fill(TransTableEntry::default());
+            }
+        }
+    }
+
+    /// 山札残り `pile_len` 枚の状態 `state` を記録する。
+    ///
+    /// 同一局面に (フレーム数, 所持金) の両方で劣らない記録が既にある場合、何もせず `false` を返す。
+    fn try_insert(&self, state: &State, pile_len: usize) -> bool {
+        let hash = state.board().zobrist_hash();
+        let shard = (hash % Self::SHARD_COUNT as u64) as usize;
+        let slot = (hash / Self::SHARD_COUNT as u64 % Self::SHARD_SLOT_COUNT as u64) as usize;
+
+        let mut shard = self.shards[shard].lock().unwrap();
+        if shard.is_empty() {
+            shard.resize(Self::SHARD_SLOT_COUNT, TransTableEntry::default());
+        }
+        let entry = &mut shard[slot];
+
+        let board = state.board().pack();
+        let pile_len = pile_len as u8;
+        let (frame, money) = (state.frame(), state.money());
+
+        let vacant = entry.generation != self.generation || entry.front.is_empty();
+        if vacant || entry.board != board || entry.pile_len != pile_len {
+            if vacant || pile_len >= entry.pile_len {
+                entry.generation = self.generation;
+                entry.board = board;
+                entry.pile_len = pile_len;
+                entry.front.clear();
+                entry.front.push((frame, money));
+            }
+            return true;
+        }
+
+        if entry.front.iter().any(|&(f, m)| f <= frame && m >= money) {
+            return false;
+        }
+        entry
+            .front
+            .retain(|&mut (f, m)| !(frame <= f && money >= m));
+        if entry.front.is_full() {
+            // フレーム数が最大のもの (枝刈りに最も効きにくいもの) を捨てる。
+            let i = (0..entry.front.len())
+                .max_by_key(|&i| entry.front[i].0)
+                .unwrap();
+            entry.front.swap_remove(i);
+        }
+        entry.front.push((frame, money));
+
+        true
+    }
 }
 
-fn print_answer(state: &State) {
-    println!("{}\t{}\t{}", state.frame(), state.money(), state.solution());
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::board::Board;
+    use crate::card::*;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+    use crate::solution::Solution;
+
+    use super::*;
+
+    /// 枝刈りなしで全ての手順を列挙し、最速解のフレーム数を返す。
+    fn solve_naive(
+        level: Level,
+        cond: ClearCondition,
+        pile: &mut CardPile,
+        state: State,
+    ) -> Option<Frame> {
+        let Some(card) = pile.pop() else {
+            return cond
+                .is_cleared(state.money(), state.board())
+                .then_some(state.frame());
+        };
+
+        let ply = level.rules().ply_count() - 1 - pile.len();
+        let res = state
+            .neighbors(ply, card, &LinearFrameModel::DEFAULT)
+            .into_iter()
+            .filter_map(|neighbor| solve_naive(level, cond, pile, neighbor))
+            .min();
+
+        pile.push(card);
+
+        res
+    }
+
+    #[test]
+    fn test_solve_endgame_optimal() {
+        let level = LEVEL_1;
+        let cond = level.rules().clear_condition(Some(30)).unwrap();
+        let board: Board = indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2S3..H9..
+        "}
+        .parse()
+        .unwrap();
+        let pile = CardPile::from_cards(&[CARD_S4, CARD_C9, CARD_D9, CARD_HK, CARD_CK, CARD_DK]);
+        // 必要所持金は満たした状態から始め、盤面を空にする最速解を求める。
+        // 必要所持金による枝刈りは test_solve_endgame_money_required で確かめる。
+        let state = State::new(0, cond.money_required(), board, Solution::new());
+
+        let expected = solve_naive(level, cond, &mut pile.clone(), state.clone());
+        assert!(expected.is_some(), "テストケースに解が存在しない");
+
+        let sink = CollectSink::new();
+        let frame = solve_endgame(
+            level,
+            cond,
+            pile,
+            state,
+            Frame::MAX,
+            &LinearFrameModel::DEFAULT,
+            &sink,
+        );
+        let answers = sink.into_answers();
+
+        assert_eq!(frame, expected.unwrap());
+        assert_eq!(answers.last().map(State::frame), expected);
+        assert!(answers.is_sorted_by(|lhs, rhs| lhs.frame() > rhs.frame()));
+    }
+
+    #[test]
+    fn test_solve_endgame_money_required() {
+        let level = LEVEL_1;
+        let board: Board = indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2S3..H9..
+        "}
+        .parse()
+        .unwrap();
+        let pile = CardPile::from_cards(&[CARD_HQ, CARD_ST, CARD_HA, CARD_S6, CARD_S4, CARD_SK]);
+        let state = State::new(0, 0, board, Solution::new());
+
+        // 最速解では所持金 3 しか得られず、それより多く得るには遅い解が必要になる。所持金 52 を超える解はない。
+        let cases = [
+            (0, Some(750)),
+            (3, Some(750)),
+            (4, Some(798)),
+            (52, Some(798)),
+            (53, None),
+        ];
+
+        for (money_required, expected) in cases {
+            let cond = level.rules().clear_condition(Some(money_required)).unwrap();
+            assert_eq!(
+                solve_naive(level, cond, &mut pile.clone(), state.clone()),
+                expected
+            );
+
+            let frame = solve_endgame(
+                level,
+                cond,
+                pile.clone(),
+                state.clone(),
+                Frame::MAX,
+                &LinearFrameModel::DEFAULT,
+                &|_: &State| {},
+            );
+
+            assert_eq!(
+                frame,
+                expected.unwrap_or(Frame::MAX),
+                "money_required={money_required}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_endgame_all_progress() {
+        /// 進捗の通知を全て集める。
+        #[derive(Default)]
+        struct ProgressSink(Mutex<Vec<(usize, usize)>>);
+
+        impl EndgameSink for ProgressSink {
+            fn on_answer(&self, _: &State) {}
+
+            fn on_progress(&self, done: usize, total: usize) {
+                self.0.lock().unwrap().push((done, total));
+            }
+        }
+
+        let level = LEVEL_1;
+        let cond = level.rules().clear_condition(Some(0)).unwrap();
+        let pile = CardPile::from_cards(&[CARD_HQ, CARD_ST, CARD_HA, CARD_S6, CARD_S4, CARD_SK]);
+        let states: Vec<_> = ["S2", "S3", "H9"]
+            .into_iter()
+            .map(|card| {
+                let board: Board = format!("{}{card}........\n", "..........\n".repeat(4))
+                    .parse()
+                    .unwrap();
+                State::new(0, 0, board, Solution::new())
+            })
+            .collect();
+
+        let sink = ProgressSink::default();
+        solve_endgame_all(
+            level,
+            cond,
+            &pile,
+            states,
+            Frame::MAX,
+            &LinearFrameModel::DEFAULT,
+            &sink,
+        );
+
+        // 反復ごとに 3 状態分の通知がある。
+        let mut progress = sink.0.into_inner().unwrap();
+        assert!(!progress.is_empty());
+        assert_eq!(progress.len() % 3, 0);
+        progress.sort_unstable();
+        assert!(progress
+            .iter()
+            .all(|&(done, total)| (1..=3).contains(&done) && total == 3));
+    }
+
+    /// 長い完全読みの所要時間を測る。リリースビルドで `cargo test --release -- --ignored` として実行する。
+    #[test]
+    #[ignore]
+    fn test_solve_endgame_long() {
+        use std::time::{Duration, Instant};
+
+        use rand::prelude::*;
+
+        use crate::evaluator::ParamEvaluator;
+        use crate::midgame::solve_midgame;
+
+        let model = LinearFrameModel::DEFAULT;
+        let mut cards = Card::all();
+        cards.shuffle(&mut SmallRng::seed_from_u64(42));
+
+        for level in [LEVEL_9, LEVEL_10] {
+            // 実際の必要所持金と盤面を空にする条件の下で測る。
+            let cond = level.rules().clear_condition(None).unwrap();
+
+            for endgame_len in [10, ENDGAME_PLY_COUNT_MAX] {
+                let (mut states, pile) = solve_midgame(
+                    level,
+                    0,
+                    CardPile::new_initial(cards),
+                    level.rules().ply_count() - endgame_len,
+                    20000,
+                    usize::MAX,
+                    42,
+                    &model,
+                    &ParamEvaluator::default(),
+                );
+                states.truncate(20);
+
+                let start = Instant::now();
+                let frame = solve_endgame_all(
+                    level,
+                    cond,
+                    &pile,
+                    states,
+                    Frame::MAX,
+                    &model,
+                    &|_: &State| {},
+                );
+                let elapsed = start.elapsed();
+                eprintln!(
+                    "level={level:?} endgame_len={endgame_len} frame={frame} elapsed={elapsed:?}"
+                );
+
+                assert!(elapsed < Duration::from_secs(60));
+            }
+        }
+    }
+
+    #[test]
+    fn test_trans_table() {
+        let mut table = TransTable::new();
+        let state = |frame, money| State::new(frame, money, Board::new(), Solution::new());
+
+        assert!(table.try_insert(&state(100, 10), 5));
+        // 両方で劣らない記録があれば弾かれる。
+        assert!(!table.try_insert(&state(100, 10), 5));
+        assert!(!table.try_insert(&state(110, 5), 5));
+        // どちらかで勝っていれば記録される。
+        assert!(table.try_insert(&state(90, 0), 5));
+        assert!(table.try_insert(&state(120, 20), 5));
+        // 山札残り枚数が異なれば別の局面。
+        assert!(table.try_insert(&state(200, 0), 4));
+
+        // 同じスロットでは、山札残り枚数が少ない局面は多い局面を置き換えない (記録されない)。
+        assert!(!table.try_insert(&state(110, 5), 5));
+        assert!(table.try_insert(&state(100, 10), 4));
+        assert!(table.try_insert(&state(100, 10), 4));
+
+        // 山札残り枚数が多い局面は置き換える。
+        assert!(table.try_insert(&state(300, 0), 6));
+        assert!(!table.try_insert(&state(300, 0), 6));
+        assert!(table.try_insert(&state(100, 10), 5));
+
+        // 消去後は以前の記録に弾かれない。世代が一周しても同様。
+        for _ in 0..=u8::MAX {
+            table.clear();
+            assert!(table.try_insert(&state(100, 10), 5));
+            assert!(!table.try_insert(&state(100, 10), 5));
+        }
+    }
 }
@@ -0,0 +1,318 @@
+//! 中盤探索の評価関数。
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+use rand::prelude::*;
+
+use crate::level::Level;
+use crate::state::State;
+
+/// 中盤探索 (ビームサーチ) の評価関数。
+///
+/// 中盤探索はメモリ節約のため手順を保持しないので、評価関数に渡される状態の手順 ([`State::solution`]) は常に空である。
+/// 評価は盤面・フレーム数・所持金のみから行うこと。
+///
+/// 探索は複数スレッドで行われるため `Sync` を要求する。
+pub trait Evaluator: Sync {
+    /// レベル `level` の `ply` 手目 (0-based) の着手後の状態 `state` を評価する。値が大きいほど有望。
+    ///
+    /// `state` の手順は空である。乱数が必要なら `rng` を用いること (結果の再現性のため)。NaN を返してはならない。
+    fn eval(&self, rng: &mut SmallRng, level: Level, ply: usize, state: &State) -> f64;
+
+    /// 盤面を空にする必要があるレベルの中盤探索で、盤面を空にできる見込みのない状態
+    /// ([`can_clear_board`](crate::can_clear_board) が偽となるもの) を捨てるかどうかを返す。
+    ///
+    /// 捨てた状態は評価されず、ビームの枠を消費しない。デフォルトでは捨てる。
+    fn prune_unclearable(&self) -> bool {
+        true
+    }
+}
+
+/// 着手後の山札残り枚数に応じて値が変わるパラメータ。
+///
+/// (山札残り枚数の下限, 値) の組を下限の降順に並べたもので、残り枚数が下限以上となる最初の組の値をとる。
+/// 該当する組がなければ 0 とする。
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlySchedule(Vec<(usize, f64)>);
+
+impl PlySchedule {
+    /// (山札残り枚数の下限, 値) の組たちからスケジュールを作る。下限は狭義降順でなければならない。
+    pub fn new(steps: impl Into<Vec<(usize, f64)>>) -> Self {
+        let steps = steps.into();
+        assert!(
+            steps.windows(2).all(|w| w[0].0 > w[1].0),
+            "下限が狭義降順でない: {steps:?}"
+        );
+
+        Self(steps)
+    }
+
+    /// (山札残り枚数の下限, 値) の組たちを返す。
+    pub fn steps(&self) -> &[(usize, f64)] {
+        &self.0
+    }
+
+    /// 山札残り枚数 `ply_rest` に対する値を返す。
+    pub fn get(&self, ply_rest: usize) -> f64 {
+        self.0
+            .iter()
+            .find(|&&(rest_min, _)| ply_rest >= rest_min)
+            .map_or(0.0, |&(_, value)| value)
+    }
+
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut steps = Vec::<(usize, f64)>::new();
+
+        for token in s.split_ascii_whitespace() {
+            let (rest_min, value) = token
+                .split_once(':')
+                .ok_or_else(|| anyhow!("'rest:value' 形式でない: '{token}'"))?;
+            let rest_min: usize = rest_min
+                .parse()
+                .with_context(|| format!("無効な山札残り枚数: '{token}'"))?;
+            let value: f64 = value
+                .parse()
+                .with_context(|| format!("無効な値: '{token}'"))?;
+            ensure!(value.is_finite(), "値が有限でない: '{token}'");
+            steps.push((rest_min, value));
+        }
+
+        ensure!(
+            steps.windows(2).all(|w| w[0].0 > w[1].0),
+            "山札残り枚数が狭義降順でない: '{s}'"
+        );
+
+        Ok(Self(steps))
+    }
+}
+
+/// "14:0 0:50" のような形式 (山札残り枚数の下限と値の組を空白区切りで並べたもの) をパースする。
+impl std::str::FromStr for PlySchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for PlySchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (rest_min, value)) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{rest_min}:{value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// パラメータで重みを調整できる評価関数。
+///
+/// 評価値は `-frame_weight * フレーム数 + money_weight * 所持金 - card_count_weight * 盤面の枚数 + 一様乱数`
+/// で、乱数は `[0, noise)` の範囲。`card_count_weight` と `noise` は山札残り枚数に応じて変わる。
+///
+/// デフォルト値はレベル 9 (45 手) で調整したもの。レベル 10 およびレベル 8 以下でも流用している。
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamEvaluator {
+    /// フレーム数 1 あたりの減点。
+    pub frame_weight: f64,
+    /// 所持金 1 あたりの加点。所持金は特に意識しなくても足りるようなので、デフォルトでは 0。
+    pub money_weight: f64,
+    /// 盤面のカード 1 枚あたりの減点。
+    pub card_count_weight: PlySchedule,
+    /// 一様乱数の幅。
+    pub noise: PlySchedule,
+    /// 盤面を空にできる見込みのない状態を捨てるかどうか ([`Evaluator::prune_unclearable`])。
+    pub prune_unclearable: bool,
+}
+
+impl ParamEvaluator {
+    /// パラメータファイル `path` から読み込む。`path` が `None` ならデフォルトのパラメータを返す。
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("評価関数パラメータ '{}' を読み取れない", path.display()))?;
+        s.parse()
+            .with_context(|| format!("評価関数パラメータ '{}' のパースに失敗", path.display()))
+    }
+
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut evaluator = Self::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("{} 行目が 'key = value' 形式でない: '{line}'", i + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let parse_weight = |value: &str| -> anyhow::Result<f64> {
+                let weight: f64 = value
+                    .parse()
+                    .with_context(|| format!("{} 行目の値が無効: '{value}'", i + 1))?;
+                ensure!(
+                    weight.is_finite(),
+                    "{} 行目の値が有限でない: '{value}'",
+                    i + 1
+                );
+                Ok(weight)
+            };
+            match key {
+                "frame_weight" => evaluator.frame_weight = parse_weight(value)?,
+                "money_weight" => evaluator.money_weight = parse_weight(value)?,
+                "card_count_weight" => {
+                    evaluator.card_count_weight = value
+                        .parse()
+                        .with_context(|| format!("{} 行目のスケジュールが無効", i + 1))?
+                }
+                "noise" => {
+                    evaluator.noise = value
+                        .parse()
+                        .with_context(|| format!("{} 行目のスケジュールが無効", i + 1))?
+                }
+                "prune_unclearable" => {
+                    evaluator.prune_unclearable = value
+                        .parse()
+                        .with_context(|| format!("{} 行目の値が無効: '{value}'", i + 1))?
+                }
+                _ => bail!("{} 行目: 未知のパラメータ: '{key}'", i + 1),
+            }
+        }
+
+        Ok(evaluator)
+    }
+}
+
+impl Default for ParamEvaluator {
+    fn default() -> Self {
+        Self {
+            frame_weight: 1.0,
+            money_weight: 0.0,
+            card_count_weight: PlySchedule::new([(14, 0.0), (0, 50.0)]),
+            noise: PlySchedule::new([(24, 300.0), (14, 200.0), (9, 100.0), (0, 50.0)]),
+            prune_unclearable: true,
+        }
+    }
+}
+
+impl Evaluator for ParamEvaluator {
+    fn eval(&self, rng: &mut SmallRng, level: Level, ply: usize, state: &State) -> f64 {
+        // 手数の区切りは着手後の山札残り枚数基準なので、レベルごとの手数の違いを吸収できる。
+        let ply_rest = level.rules().ply_count() - 1 - ply;
+
+        let frame = f64::from(state.frame());
+        let money = f64::from(state.money());
+        let card_count = f64::from(state.card_count());
+
+        let noise = self.noise.get(ply_rest);
+        let value_rand = if noise > 0.0 {
+            rng.gen_range(0.0..noise)
+        } else {
+            0.0
+        };
+
+        -self.frame_weight * frame + self.money_weight * money
+            - self.card_count_weight.get(ply_rest) * card_count
+            + value_rand
+    }
+
+    fn prune_unclearable(&self) -> bool {
+        self.prune_unclearable
+    }
+}
+
+/// 1 行 1 パラメータの "key = value" 形式をパースする。`#` 以降はコメント。
+/// 指定されなかったパラメータはデフォルト値となる。
+///
+/// 例:
+///
+/// ```text
+/// frame_weight = 1
+/// card_count_weight = 14:0 0:50
+/// noise = 24:300 14:200 9:100 0:50
+/// prune_unclearable = true
+/// ```
+impl std::str::FromStr for ParamEvaluator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for ParamEvaluator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "frame_weight = {}", self.frame_weight)?;
+        writeln!(f, "money_weight = {}", self.money_weight)?;
+        writeln!(f, "card_count_weight = {}", self.card_count_weight)?;
+        writeln!(f, "noise = {}", self.noise)?;
+        writeln!(f, "prune_unclearable = {}", self.prune_unclearable)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_ply_schedule() {
+        let schedule: PlySchedule = "24:300 14:200 0:50".parse().unwrap();
+        assert_eq!(schedule.get(30), 300.0);
+        assert_eq!(schedule.get(24), 300.0);
+        assert_eq!(schedule.get(23), 200.0);
+        assert_eq!(schedule.get(0), 50.0);
+
+        assert_eq!("".parse::<PlySchedule>().unwrap().get(0), 0.0);
+        assert_eq!("5:1".parse::<PlySchedule>().unwrap().get(4), 0.0);
+
+        assert!("0:1 5:2".parse::<PlySchedule>().is_err());
+        assert!("5".parse::<PlySchedule>().is_err());
+    }
+
+    #[test]
+    fn test_param_evaluator_io() {
+        let evaluator = ParamEvaluator::default();
+        assert_eq!(
+            evaluator.to_string().parse::<ParamEvaluator>().unwrap(),
+            evaluator
+        );
+
+        assert_eq!("".parse::<ParamEvaluator>().unwrap(), evaluator);
+
+        let evaluator: ParamEvaluator = indoc! {"
+            # コメント
+            money_weight = 0.5
+            noise = 10:100 0:0  # 終盤は乱数なし
+            prune_unclearable = false
+        "}
+        .parse()
+        .unwrap();
+        assert_eq!(
+            evaluator,
+            ParamEvaluator {
+                money_weight: 0.5,
+                noise: PlySchedule::new([(10, 100.0), (0, 0.0)]),
+                prune_unclearable: false,
+                ..ParamEvaluator::default()
+            }
+        );
+
+        assert!("frame_weight".parse::<ParamEvaluator>().is_err());
+        assert!("foo = 1".parse::<ParamEvaluator>().is_err());
+        assert!("noise = 1".parse::<ParamEvaluator>().is_err());
+        assert!("prune_unclearable = 1".parse::<ParamEvaluator>().is_err());
+    }
+}
@@ -0,0 +1,466 @@
+//! 記録されたプレイのトレースからの `LinearFrameModel` のパラメータ推定。
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+
+use crate::board::Board;
+use crate::card::Card;
+use crate::frame_model::{FrameModel, LinearFrameModel};
+use crate::square::{Col, COL_A, COL_B, COL_C, COL_D, COL_E};
+use crate::yaku::process_yaku_chain;
+use crate::Frame;
+
+/// トレース中の 1 回の着手。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEvent {
+    board: Board,
+    card: Card,
+    col: Col,
+    frame: Frame,
+}
+
+impl TraceEvent {
+    /// 着手前の盤面 `board` で列 `col` にツモ `card` を置き、次の入力受付まで `frame` フレームかかったことを表すイベントを作る。
+    /// `board` は全てのカードが落下済みで、着手が有効でなければならない。
+    pub fn new(board: Board, card: Card, col: Col, frame: Frame) -> anyhow::Result<Self> {
+        ensure!(
+            board.put(col, card).is_some(),
+            "列 {col} にカードを置けない:\n{board}"
+        );
+
+        Ok(Self {
+            board,
+            card,
+            col,
+            frame,
+        })
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn card(&self) -> Card {
+        self.card
+    }
+
+    pub fn col(&self) -> Col {
+        self.col
+    }
+
+    /// 観測されたフレーム数を返す。
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// `model` で予測されるフレーム数を返す。
+    fn simulate(&self, model: &LinearFrameModel) -> Frame {
+        let (mut board, row) = self.board.put(self.col, self.card).unwrap();
+        let (frame_yaku, _) = process_yaku_chain(&mut board, model);
+
+        model.put(row) + frame_yaku
+    }
+
+    /// 連鎖数 (役が成立した回数) を返す。
+    fn chain_count(&self) -> usize {
+        // 役成立演出のみを 1 フレームとしたモデルでのフレーム数が連鎖数となる。
+        usize::from(self.simulate(&basis_model(3)))
+    }
+
+    /// 各パラメータに掛かる係数 (特徴量) を返す。
+    fn features(&self) -> [f64; PARAM_COUNT] {
+        std::array::from_fn(|i| f64::from(self.simulate(&basis_model(i))))
+    }
+
+    /// "盤面\tツモ\t列\tフレーム数" 形式の 1 行をパースする。盤面は上の行から順に '/' 区切りで書く。
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let fields: Vec<_> = s.split('\t').collect();
+        ensure!(fields.len() == 4, "フィールド数が 4 でない: '{s}'");
+
+        let board: Board = fields[0].replace('/', "\n").parse()?;
+        let card: Card = fields[1].parse()?;
+        let col = match fields[2] {
+            "A" => COL_A,
+            "B" => COL_B,
+            "C" => COL_C,
+            "D" => COL_D,
+            "E" => COL_E,
+            _ => bail!("無効な列文字列: '{}'", fields[2]),
+        };
+        let frame: Frame = fields[3]
+            .parse()
+            .with_context(|| format!("無効なフレーム数: '{}'", fields[3]))?;
+
+        Self::new(board, card, col, frame)
+    }
+}
+
+impl std::str::FromStr for TraceEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// 推定対象のパラメータ数。
+const PARAM_COUNT: usize = 6;
+
+/// 推定対象のパラメータ名。
+const PARAM_NAMES: [&str; PARAM_COUNT] = [
+    "put_base",
+    "put_per_row",
+    "fall_per_square",
+    "yaku_base",
+    "yaku_per_card",
+    "yaku_per_chain",
+];
+
+/// `i` 番目のパラメータのみを 1 とし、他を 0 としたモデルを返す。
+fn basis_model(i: usize) -> LinearFrameModel {
+    let mut params = [0; PARAM_COUNT];
+    params[i] = 1;
+
+    LinearFrameModel {
+        put_base: params[0],
+        put_per_row: params[1],
+        fall_per_square: params[2],
+        yaku_base: params[3],
+        yaku_per_card: params[4],
+        yaku_per_chain: params[5],
+    }
+}
+
+/// イベントの種類 (残差の集計単位)。
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum TraceEventKind {
+    /// 役が成立しない着手。
+    Put,
+    /// 役が 1 回だけ成立する着手。
+    Yaku,
+    /// 連鎖が発生する着手。
+    Chain,
+}
+
+impl TraceEventKind {
+    pub const fn all() -> [Self; 3] {
+        [Self::Put, Self::Yaku, Self::Chain]
+    }
+
+    fn from_chain_count(chain_count: usize) -> Self {
+        match chain_count {
+            0 => Self::Put,
+            1 => Self::Yaku,
+            _ => Self::Chain,
+        }
+    }
+}
+
+/// イベントの種類ごとの残差の統計。
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidualStats {
+    kind: TraceEventKind,
+    count: usize,
+    mean: f64,
+    rms: f64,
+    max_abs: f64,
+}
+
+impl ResidualStats {
+    pub fn kind(&self) -> TraceEventKind {
+        self.kind
+    }
+
+    /// イベント数を返す。
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// 残差 (観測値 - 予測値) の平均を返す。
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// 残差の二乗平均平方根を返す。
+    pub fn rms(&self) -> f64 {
+        self.rms
+    }
+
+    /// 残差の絶対値の最大値を返す。
+    pub fn max_abs(&self) -> f64 {
+        self.max_abs
+    }
+}
+
+/// パラメータ推定結果。
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameModelFit {
+    params: [f64; PARAM_COUNT],
+    residuals: Vec<ResidualStats>,
+}
+
+impl FrameModelFit {
+    /// 推定されたパラメータたちを (名前, 値) の形で返す。
+    pub fn params(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+        std::iter::zip(PARAM_NAMES, self.params)
+    }
+
+    /// 推定されたパラメータを四捨五入したモデルを返す。
+    pub fn model(&self) -> LinearFrameModel {
+        let [put_base, put_per_row, fall_per_square, yaku_base, yaku_per_card, yaku_per_chain] =
+            self.params
+                .map(|x| x.round().clamp(0.0, f64::from(Frame::MAX)) as Frame);
+
+        LinearFrameModel {
+            put_base,
+            put_per_row,
+            fall_per_square,
+            yaku_base,
+            yaku_per_card,
+            yaku_per_chain,
+        }
+    }
+
+    /// イベントの種類ごとの残差の統計を返す (イベントが存在する種類のみ)。
+    pub fn residuals(&self) -> &[ResidualStats] {
+        &self.residuals
+    }
+}
+
+/// トレースから最小二乗法で `LinearFrameModel` のパラメータを推定する。
+///
+/// トレース中で一度も効かないパラメータ (例: 役が一度も成立しない場合の `yaku_base`) は
+/// `model_default` の値に固定する。
+pub fn fit_frame_model(
+    events: &[TraceEvent],
+    model_default: &LinearFrameModel,
+) -> anyhow::Result<FrameModelFit> {
+    ensure!(!events.is_empty(), "トレースが空");
+
+    let features: Vec<_> = events.iter().map(TraceEvent::features).collect();
+    let defaults = model_params(model_default);
+
+    // 有効なパラメータ (特徴量が 0 でないイベントが存在するもの) のみを推定する。
+    let active: Vec<usize> = (0..PARAM_COUNT)
+        .filter(|&i| features.iter().any(|xs| xs[i] != 0.0))
+        .collect();
+
+    // 正規方程式 (A^T A) x = A^T b を立てる。固定パラメータの寄与は b から引いておく。
+    let n = active.len();
+    let mut ata = vec![vec![0.0; n]; n];
+    let mut atb = vec![0.0; n];
+    for (event, xs) in std::iter::zip(events, &features) {
+        let fixed: f64 = (0..PARAM_COUNT)
+            .filter(|i| !active.contains(i))
+            .map(|i| xs[i] * defaults[i])
+            .sum();
+        let b = f64::from(event.frame()) - fixed;
+        for (r, &i) in active.iter().enumerate() {
+            for (c, &j) in active.iter().enumerate() {
+                ata[r][c] += xs[i] * xs[j];
+            }
+            atb[r] += xs[i] * b;
+        }
+    }
+
+    let solution = solve_linear(ata, atb).ok_or_else(|| {
+        anyhow!("正規方程式が特異 (パラメータを一意に定めるのに十分な種類のイベントがない)")
+    })?;
+
+    let mut params = defaults;
+    for (&i, x) in std::iter::zip(&active, solution) {
+        params[i] = x;
+    }
+
+    let residuals = residual_stats(events, &features, &params);
+
+    Ok(FrameModelFit { params, residuals })
+}
+
+fn model_params(model: &LinearFrameModel) -> [f64; PARAM_COUNT] {
+    [
+        model.put_base,
+        model.put_per_row,
+        model.fall_per_square,
+        model.yaku_base,
+        model.yaku_per_card,
+        model.yaku_per_chain,
+    ]
+    .map(f64::from)
+}
+
+fn residual_stats(
+    events: &[TraceEvent],
+    features: &[[f64; PARAM_COUNT]],
+    params: &[f64; PARAM_COUNT],
+) -> Vec<ResidualStats> {
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| TraceEventKind::from_chain_count(event.chain_count()))
+        .collect();
+
+    TraceEventKind::all()
+        .into_iter()
+        .filter_map(|kind| {
+            let residuals: Vec<f64> = itertools::izip!(events, features, &kinds)
+                .filter(|(_, _, &k)| k == kind)
+                .map(|(event, xs, _)| {
+                    let predict: f64 = std::iter::zip(xs, params).map(|(x, p)| x * p).sum();
+                    f64::from(event.frame()) - predict
+                })
+                .collect();
+            if residuals.is_empty() {
+                return None;
+            }
+            let count = residuals.len();
+            let mean = residuals.iter().sum::<f64>() / count as f64;
+            let rms = (residuals.iter().map(|r| r * r).sum::<f64>() / count as f64).sqrt();
+            let max_abs = residuals.iter().map(|r| r.abs()).fold(0.0, f64::max);
+            Some(ResidualStats {
+                kind,
+                count,
+                mean,
+                rms,
+                max_abs,
+            })
+        })
+        .collect()
+}
+
+/// 連立一次方程式 `a x = b` を部分ピボット選択付きガウスの消去法で解く。特異なら `None` を返す。
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    const EPS: f64 = 1e-9;
+
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < EPS {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in std::iter::zip(&mut row[col..], &pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + i] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::card::*;
+    use crate::square::*;
+
+    use super::*;
+
+    fn parse_board(s: impl AsRef<str>) -> Board {
+        s.as_ref().parse().unwrap()
+    }
+
+    #[test]
+    fn test_trace_event_io() {
+        let event: TraceEvent = "........../........../........../........../..........\tSA\tC\t37"
+            .parse()
+            .unwrap();
+        assert_eq!(event.board(), &Board::new());
+        assert_eq!(event.card(), CARD_SA);
+        assert_eq!(event.col(), COL_C);
+        assert_eq!(event.frame(), 37);
+
+        assert!(
+            "........../........../........../........../..........\tSA\tF\t37"
+                .parse::<TraceEvent>()
+                .is_err()
+        );
+        assert!("SA\tC\t37".parse::<TraceEvent>().is_err());
+    }
+
+    #[test]
+    fn test_fit_frame_model() {
+        let model_true = LinearFrameModel {
+            put_base: 30,
+            put_per_row: 12,
+            fall_per_square: 6,
+            yaku_base: 80,
+            yaku_per_card: 5,
+            yaku_per_chain: 10,
+        };
+
+        let board_3 = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            H2S2......
+        "});
+        let board_4 = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            H2S2D2....
+        "});
+        let board_fall = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            SKDQ......
+            H2S2......
+        "});
+        let board_chain = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            ..SKHK....
+            DKH2S2....
+        "});
+
+        let cases = [
+            (Board::new(), CARD_SA, COL_A),
+            (board_fall.clone(), CARD_C9, COL_A),
+            (board_fall.clone(), CARD_C9, COL_E),
+            (board_3.clone(), CARD_C2, COL_C),
+            (board_4.clone(), CARD_C2, COL_D),
+            (board_fall.clone(), CARD_C2, COL_C),
+            (board_chain.clone(), CARD_C2, COL_D),
+        ];
+
+        let events: Vec<_> = cases
+            .into_iter()
+            .map(|(board, card, col)| {
+                let (mut after, row) = board.put(col, card).unwrap();
+                let (frame_yaku, _) = process_yaku_chain(&mut after, &model_true);
+                TraceEvent::new(board, card, col, model_true.put(row) + frame_yaku).unwrap()
+            })
+            .collect();
+
+        let fit = fit_frame_model(&events, &LinearFrameModel::DEFAULT).unwrap();
+        assert_eq!(fit.model(), model_true);
+        assert_eq!(
+            fit.residuals()
+                .iter()
+                .map(ResidualStats::kind)
+                .collect::<Vec<_>>(),
+            TraceEventKind::all()
+        );
+        for stats in fit.residuals() {
+            assert!(stats.max_abs() < 1e-6, "{stats:?}");
+        }
+    }
+}
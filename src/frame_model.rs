@@ -0,0 +1,187 @@
+//! フレームコストのモデル。
+
+use anyhow::{anyhow, bail, Context as _};
+
+use crate::square::Row;
+use crate::Frame;
+
+/// フレームコストのモデル。
+///
+/// 着手 1 回のフレームコストは `put` と、役が成立するごとの `yaku` および `fall` の和となる。
+///
+/// 探索は複数スレッドで行われるため `Sync` を要求する。
+pub trait FrameModel: Sync {
+    /// ツモを `row` の高さに置く (ツモ出現から着地まで) のにかかるフレーム数を返す。
+    fn put(&self, row: Row) -> Frame;
+
+    /// 空中にあるカードたちが合計 `dist` マス落下するのにかかるフレーム数を返す。
+    fn fall(&self, dist: usize) -> Frame;
+
+    /// 連鎖の `chain` 段目 (0-based) で `card_count` 枚のカードが役として消去されるのにかかるフレーム数を返す。
+    /// 消去後の落下は含まない。
+    fn yaku(&self, chain: usize, card_count: usize) -> Frame;
+}
+
+/// 定数係数の線形なフレームコストのモデル。
+///
+/// デフォルト値は原作の挙動からの概算。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LinearFrameModel {
+    /// ツモを最上段に置くのにかかるフレーム数。
+    pub put_base: Frame,
+    /// 置く高さが 1 段下がるごとに増えるフレーム数。
+    pub put_per_row: Frame,
+    /// カード 1 枚が 1 マス落下するのにかかるフレーム数。
+    pub fall_per_square: Frame,
+    /// 役成立演出のフレーム数。
+    pub yaku_base: Frame,
+    /// カード 1 枚の消去にかかるフレーム数。
+    pub yaku_per_card: Frame,
+    /// 連鎖が 1 段深くなるごとに役成立演出に加わるフレーム数。
+    pub yaku_per_chain: Frame,
+}
+
+impl LinearFrameModel {
+    pub const DEFAULT: Self = Self {
+        put_base: 37,
+        put_per_row: 16,
+        fall_per_square: 8,
+        yaku_base: 72,
+        yaku_per_card: 8,
+        yaku_per_chain: 0,
+    };
+
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut model = Self::DEFAULT;
+
+        for token in s
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+        {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| anyhow!("'key=value' 形式でない: '{token}'"))?;
+            let value: Frame = value
+                .trim()
+                .parse()
+                .with_context(|| format!("無効な値: '{token}'"))?;
+            let field = match key.trim() {
+                "put_base" => &mut model.put_base,
+                "put_per_row" => &mut model.put_per_row,
+                "fall_per_square" => &mut model.fall_per_square,
+                "yaku_base" => &mut model.yaku_base,
+                "yaku_per_card" => &mut model.yaku_per_card,
+                "yaku_per_chain" => &mut model.yaku_per_chain,
+                _ => bail!("未知のパラメータ: '{key}'"),
+            };
+            *field = value;
+        }
+
+        Ok(model)
+    }
+}
+
+impl Default for LinearFrameModel {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// パラメータは利用者が与えうるので、オーバーフローする場合は `Frame::MAX` に飽和させる。
+impl FrameModel for LinearFrameModel {
+    fn put(&self, row: Row) -> Frame {
+        let depth = (Row::NUM - 1 - row.to_index()) as Frame;
+        self.put_base
+            .saturating_add(self.put_per_row.saturating_mul(depth))
+    }
+
+    fn fall(&self, dist: usize) -> Frame {
+        self.fall_per_square.saturating_mul(saturate(dist))
+    }
+
+    fn yaku(&self, chain: usize, card_count: usize) -> Frame {
+        self.yaku_base
+            .saturating_add(self.yaku_per_chain.saturating_mul(saturate(chain)))
+            .saturating_add(self.yaku_per_card.saturating_mul(saturate(card_count)))
+    }
+}
+
+/// `value` を `Frame` に変換する。収まらなければ `Frame::MAX` とする。
+fn saturate(value: usize) -> Frame {
+    Frame::try_from(value).unwrap_or(Frame::MAX)
+}
+
+/// "put_base=37,yaku_base=72" のような形式をパースする。指定されなかったパラメータはデフォルト値となる。
+impl std::str::FromStr for LinearFrameModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for LinearFrameModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "put_base={},put_per_row={},fall_per_square={},yaku_base={},yaku_per_card={},yaku_per_chain={}",
+            self.put_base,
+            self.put_per_row,
+            self.fall_per_square,
+            self.yaku_base,
+            self.yaku_per_card,
+            self.yaku_per_chain
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::square::*;
+
+    use super::*;
+
+    #[test]
+    fn test_linear_frame_model_io() {
+        let model = LinearFrameModel::default();
+        assert_eq!(
+            model.to_string().parse::<LinearFrameModel>().unwrap(),
+            model
+        );
+
+        assert_eq!("".parse::<LinearFrameModel>().unwrap(), model);
+
+        let model: LinearFrameModel = "put_base=40, yaku_per_card=6, yaku_per_chain=4"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            model,
+            LinearFrameModel {
+                put_base: 40,
+                yaku_per_card: 6,
+                yaku_per_chain: 4,
+                ..LinearFrameModel::DEFAULT
+            }
+        );
+
+        assert!("put_base".parse::<LinearFrameModel>().is_err());
+        assert!("foo=1".parse::<LinearFrameModel>().is_err());
+    }
+
+    #[test]
+    fn test_linear_frame_model_saturate() {
+        let model = LinearFrameModel::DEFAULT;
+        assert_eq!(model.put(ROW_5), 37);
+        assert_eq!(model.put(ROW_1), 37 + 4 * 16);
+
+        let model: LinearFrameModel =
+            "put_base=60000,put_per_row=30000,fall_per_square=30000,yaku_per_chain=30000"
+                .parse()
+                .unwrap();
+        assert_eq!(model.put(ROW_5), 60000);
+        assert_eq!(model.put(ROW_1), Frame::MAX);
+        assert_eq!(model.fall(3), Frame::MAX);
+        assert_eq!(model.yaku(3, 3), Frame::MAX);
+    }
+}
@@ -0,0 +1,345 @@
+//! 複数レベルを通した探索。
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::endgame::{solve_endgame_all, CollectSink};
+use crate::evaluator::Evaluator;
+use crate::frame_model::FrameModel;
+use crate::level::{ClearCondition, Level};
+use crate::midgame::solve_midgame;
+use crate::pareto::{retain_pareto_front, thin_indices};
+use crate::position::CardPile;
+use crate::state::State;
+use crate::{Frame, Money, TotalFrame};
+
+/// 複数レベルを通した解。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Route {
+    total_frame: TotalFrame,
+    money: Money,
+    states: Vec<State>,
+}
+
+impl Route {
+    fn new(money: Money) -> Self {
+        Self {
+            total_frame: 0,
+            money,
+            states: vec![],
+        }
+    }
+
+    fn add_level(&self, state: State) -> Self {
+        let mut states = self.states.clone();
+        let total_frame = self.total_frame + TotalFrame::from(state.frame());
+        let money = state.money();
+        states.push(state);
+
+        Self {
+            total_frame,
+            money,
+            states,
+        }
+    }
+
+    /// 全レベルの総フレーム数を返す。
+    pub fn total_frame(&self) -> TotalFrame {
+        self.total_frame
+    }
+
+    /// 最終レベル終了時の所持金を返す。
+    pub fn money(&self) -> Money {
+        self.money
+    }
+
+    /// 各レベル終了時の状態を返す。
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+}
+
+impl std::fmt::Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "total_frame={}", self.total_frame)?;
+        writeln!(f, "money={}", self.money)?;
+        for state in &self.states {
+            writeln!(
+                f,
+                "{}\t{}\t{}",
+                state.frame(),
+                state.money(),
+                state.solution()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// [`solve_game`] の探索パラメータ。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameSearchConfig {
+    /// 各レベルの中盤探索のビーム幅。
+    pub beam_width: usize,
+    /// 中盤探索で同一盤面について残す状態の個数の上限。
+    pub pareto_len_max: usize,
+    /// 各レベルの終盤完全読み手数。0 ならば中盤探索のみで最後まで探索する。
+    pub endgame_len: usize,
+    /// 各レベルで上位から何件の状態を終盤完全読みの対象とするか。
+    pub endgame_state_count: usize,
+    /// 次のレベルに引き継ぐ解の最大個数。
+    pub route_count_max: usize,
+    /// 中盤探索の評価関数用の乱数シード。
+    pub rng_seed: u64,
+}
+
+/// `level_first` から始まる連続したレベルを通しで探索し、総フレーム数の小さい順にソートされた解集合を返す。
+///
+/// `piles` は各レベルの初期山札、`conds` は各レベルのクリア条件で、`piles.len()` レベル分を探索する。
+/// 各レベルの終了時の所持金は次のレベルに持ち越される。
+///
+/// 各レベルは残り `config.endgame_len` 手まで `solve_midgame` で探索し、上位 `config.endgame_state_count` 件の状態から
+/// `solve_endgame_all` で最後まで完全読みする。
+/// そしてクリア条件を満たす状態のうち (フレーム数, 所持金) についてパレート最適なものを次のレベルに引き継ぐ。
+/// 引き継ぐ解の個数は `config.route_count_max` 以下に間引かれる。
+/// あるレベルでクリア条件を満たす解が見つからなければ、空の解集合を返す。
+/// フレームコストは `model` に、中盤探索の状態の評価は `evaluator` に従う。
+pub fn solve_game<M: FrameModel + ?Sized>(
+    level_first: Level,
+    money: Money,
+    piles: Vec<CardPile>,
+    conds: &[ClearCondition],
+    config: &GameSearchConfig,
+    model: &M,
+    evaluator: &dyn Evaluator,
+) -> Vec<Route> {
+    assert!(config.route_count_max > 0);
+    assert_eq!(piles.len(), conds.len());
+    assert!(
+        usize::from(level_first.to_inner()) + piles.len() <= usize::from(Level::MAX_VALUE) + 1,
+        "レベル {} を超える山札が与えられた",
+        Level::MAX_VALUE
+    );
+
+    let mut routes = vec![Route::new(money)];
+
+    for (i, (pile, &cond)) in piles.into_iter().zip(conds).enumerate() {
+        let level = Level::from_inner(level_first.to_inner() + i as u8).unwrap();
+
+        // 同じ所持金で始まるなら探索結果も同じなので使い回す。
+        let mut cache = HashMap::<Money, Vec<State>>::new();
+
+        let mut routes_nxt = Vec::<Route>::new();
+        for route in &routes {
+            let states = cache.entry(route.money()).or_insert_with(|| {
+                solve_level(
+                    level,
+                    cond,
+                    route.money(),
+                    pile.clone(),
+                    config,
+                    model,
+                    evaluator,
+                )
+            });
+            routes_nxt.extend(states.iter().cloned().map(|state| route.add_level(state)));
+        }
+
+        routes = pareto_routes(routes_nxt, config.route_count_max);
+        if routes.is_empty() {
+            break;
+        }
+    }
+
+    routes
+}
+
+/// 1 レベルを最後まで探索し、クリア条件 `cond` を満たす状態のうち (フレーム数, 所持金) についてパレート最適なものを
+/// フレーム数昇順で返す。
+///
+/// 終盤完全読みを行う場合、パレート最適な状態はフレーム数の小さいものから高々 `config.route_count_max` 個を求める。
+fn solve_level<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    money: Money,
+    pile: CardPile,
+    config: &GameSearchConfig,
+    model: &M,
+    evaluator: &dyn Evaluator,
+) -> Vec<State> {
+    let ply_count = level.rules().ply_count();
+    assert!(config.endgame_len <= ply_count);
+
+    let (mut states, pile) = solve_midgame(
+        level,
+        money,
+        pile,
+        ply_count - config.endgame_len,
+        config.beam_width,
+        config.pareto_len_max,
+        config.rng_seed,
+        model,
+        evaluator,
+    );
+
+    if config.endgame_len == 0 {
+        states.retain(|state| cond.is_cleared(state.money(), state.board()));
+    } else {
+        states.truncate(config.endgame_state_count);
+        states = solve_endgame_front(level, cond, &pile, states, config.route_count_max, model);
+    }
+
+    retain_pareto_front(&mut states, |state| (state.frame(), state.money()));
+
+    states
+}
+
+/// 候補状態たち `states` から山札 `pile` を置き終えるまでの完全読みにより、クリア条件 `cond` を満たす状態のうち
+/// (フレーム数, 所持金) についてパレート最適なものをフレーム数の小さい順に高々 `len_max` 個求める。
+///
+/// 最速解を求めた後、必要所持金をその解の所持金より大きくして再び最速解を求めることを繰り返す。
+fn solve_endgame_front<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    pile: &CardPile,
+    states: Vec<State>,
+    len_max: usize,
+    model: &M,
+) -> Vec<State> {
+    let mut front = Vec::<State>::new();
+    let mut cond = cond;
+
+    while front.len() < len_max {
+        let sink = CollectSink::new();
+        solve_endgame_all(level, cond, pile, states.clone(), Frame::MAX, model, &sink);
+        let Some(state) = sink
+            .into_answers()
+            .into_iter()
+            .min_by_key(|state| (state.frame(), Reverse(state.money())))
+        else {
+            break;
+        };
+
+        let Some(money_required) = state.money().checked_add(1) else {
+            front.push(state);
+            break;
+        };
+        cond = cond.with_money_required(money_required);
+        front.push(state);
+    }
+
+    front
+}
+
+/// (総フレーム数, 所持金) についてパレート最適なルートたちを総フレーム数昇順で返す。
+///
+/// 個数が `count_max` を超える場合、両端を含めて等間隔に間引く。
+fn pareto_routes(mut routes: Vec<Route>, count_max: usize) -> Vec<Route> {
+    retain_pareto_front(&mut routes, |route| (route.total_frame(), route.money()));
+
+    let idxs: Vec<_> = thin_indices(routes.len(), count_max).collect();
+    routes
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| idxs.binary_search(i).is_ok())
+        .map(|(_, route)| route)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::card::Card;
+    use crate::evaluator::ParamEvaluator;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+    use crate::solution::Solution;
+
+    use super::*;
+
+    fn make_route(levels: &[(Frame, Money)]) -> Route {
+        levels.iter().fold(Route::new(0), |route, &(frame, money)| {
+            route.add_level(State::new(frame, money, Board::new(), Solution::new()))
+        })
+    }
+
+    #[test]
+    fn test_pareto_routes() {
+        let routes = vec![
+            make_route(&[(100, 10), (100, 20)]),
+            make_route(&[(90, 5), (100, 5)]),
+            make_route(&[(95, 10), (100, 10)]),
+            make_route(&[(100, 30), (150, 40)]),
+            make_route(&[(110, 30), (100, 15)]),
+        ];
+
+        let totals = |routes: &[Route]| -> Vec<(TotalFrame, Money)> {
+            routes
+                .iter()
+                .map(|route| (route.total_frame(), route.money()))
+                .collect()
+        };
+
+        let res = pareto_routes(routes.clone(), 10);
+        assert_eq!(totals(&res), [(190, 5), (195, 10), (200, 20), (250, 40)]);
+
+        let res = pareto_routes(routes.clone(), 2);
+        assert_eq!(totals(&res), [(190, 5), (250, 40)]);
+
+        let res = pareto_routes(routes, 1);
+        assert_eq!(totals(&res), [(190, 5)]);
+    }
+
+    #[test]
+    fn test_solve_game_carries_money() {
+        let piles = vec![CardPile::new_initial(Card::all()); 2];
+        // 小さいビーム幅でも解が見つかるよう、盤面を空にしなくてよいことにする。
+        let conds = [ClearCondition::new(0, false); 2];
+        let model = LinearFrameModel::DEFAULT;
+
+        let routes = solve_game(
+            LEVEL_9,
+            7,
+            piles.clone(),
+            &conds,
+            &GameSearchConfig {
+                beam_width: 100,
+                pareto_len_max: 1,
+                endgame_len: 4,
+                endgame_state_count: 20,
+                route_count_max: 3,
+                rng_seed: 42,
+            },
+            &model,
+            &ParamEvaluator::default(),
+        );
+        assert!(!routes.is_empty());
+
+        for route in &routes {
+            assert_eq!(route.states().len(), 2);
+
+            // 各レベルの解を、前のレベルの終了時の所持金から再生すると同じ状態に至る。
+            let mut money = 7;
+            let mut total_frame = 0;
+            for ((level, pile), state) in [LEVEL_9, LEVEL_10]
+                .into_iter()
+                .zip(&piles)
+                .zip(route.states())
+            {
+                let records = state
+                    .solution()
+                    .replay(level, money, pile.clone(), &model)
+                    .unwrap();
+                let last = records.last().unwrap();
+                assert_eq!(last.frame_elapsed(), state.frame());
+                assert_eq!(last.money(), state.money());
+
+                money = state.money();
+                total_frame += TotalFrame::from(state.frame());
+            }
+            assert_eq!(route.money(), money);
+            assert_eq!(route.total_frame(), total_frame);
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use crate::board::Board;
+use crate::card::Card;
 use crate::macros::assert_unchecked;
+use crate::square::*;
+use crate::Money;
 
 /// 原作のゲームレベル (`1..=10`)。
 #[repr(u8)]
@@ -28,6 +32,9 @@ pub const LEVEL_9: Level = Level::Level9;
 pub const LEVEL_10: Level = Level::Level10;
 
 impl Level {
+    /// レベルの個数。
+    pub const NUM: usize = 10;
+
     pub const MIN_VALUE: u8 = 1;
     pub const MAX_VALUE: u8 = 10;
 
@@ -56,7 +63,169 @@ impl Level {
         (self.to_inner() - Self::MIN_VALUE) as usize
     }
 
+    /// 全てのレベルを昇順で返す。
+    pub const fn all() -> [Self; Self::NUM] {
+        [
+            LEVEL_1, LEVEL_2, LEVEL_3, LEVEL_4, LEVEL_5, LEVEL_6, LEVEL_7, LEVEL_8, LEVEL_9,
+            LEVEL_10,
+        ]
+    }
+
+    /// レベルのルール (クリア条件および初期配置) を返す。
+    pub const fn rules(self) -> &'static LevelRules {
+        &LEVEL_RULES[self.to_index()]
+    }
+
     const fn is_valid(inner: u8) -> bool {
         matches!(inner, Self::MIN_VALUE..=Self::MAX_VALUE)
     }
 }
+
+/// レベルごとのルール (クリア条件および初期配置)。
+#[derive(Debug, Eq, PartialEq)]
+pub struct LevelRules {
+    initial_squares: &'static [Square],
+    disconnect_pairs: &'static [(usize, usize)],
+    money_required: Option<Money>,
+    must_clear_board: bool,
+}
+
+// レベル 5 では 2 枚、レベル 6..=7 では 3 枚初期配置される。
+// これらの場合、初期配置で役ができることはない。
+//
+// レベル 8 では 5 枚、レベル 9..=10 では 7 枚初期配置される。
+// これらの場合、初期配置で役ができないような調整 (disconnect 処理) が行われる。
+// disconnect 処理は原作準拠。なお、論理的には (4, 5) は disconnect する必要がない。
+//
+// 必要所持金は原作で確認済みのレベル 9, 10 のみ記録し、レベル 8 以下は未確認として `None` とする。
+
+const INITIAL_SQUARES_EMPTY: &[Square] = &[];
+const INITIAL_SQUARES_5: &[Square] = &[SQ_A1, SQ_E1];
+const INITIAL_SQUARES_6_TO_7: &[Square] = &[SQ_A1, SQ_E1, SQ_C1];
+const INITIAL_SQUARES_8: &[Square] = &[SQ_A1, SQ_B1, SQ_C1, SQ_D1, SQ_E1];
+const INITIAL_SQUARES_9_TO_10: &[Square] = &[SQ_A1, SQ_B1, SQ_C1, SQ_D1, SQ_E1, SQ_B2, SQ_D2];
+
+const DISCONNECT_PAIRS_EMPTY: &[(usize, usize)] = &[];
+const DISCONNECT_PAIRS_8: &[(usize, usize)] = &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5)];
+const DISCONNECT_PAIRS_9_TO_10: &[(usize, usize)] =
+    &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (1, 5), (3, 6)];
+
+const LEVEL_RULES: [LevelRules; Level::NUM] = [
+    LevelRules::new(INITIAL_SQUARES_EMPTY, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_EMPTY, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_EMPTY, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_EMPTY, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_5, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_6_TO_7, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_6_TO_7, DISCONNECT_PAIRS_EMPTY, None, true),
+    LevelRules::new(INITIAL_SQUARES_8, DISCONNECT_PAIRS_8, None, true),
+    LevelRules::new(
+        INITIAL_SQUARES_9_TO_10,
+        DISCONNECT_PAIRS_9_TO_10,
+        Some(200),
+        true,
+    ),
+    LevelRules::new(
+        INITIAL_SQUARES_9_TO_10,
+        DISCONNECT_PAIRS_9_TO_10,
+        Some(250),
+        true,
+    ),
+];
+
+impl LevelRules {
+    const fn new(
+        initial_squares: &'static [Square],
+        disconnect_pairs: &'static [(usize, usize)],
+        money_required: Option<Money>,
+        must_clear_board: bool,
+    ) -> Self {
+        Self {
+            initial_squares,
+            disconnect_pairs,
+            money_required,
+            must_clear_board,
+        }
+    }
+
+    /// 初期配置されるマスたちを返す。山札から取り出される順に並んでいる。
+    pub const fn initial_squares(&self) -> &'static [Square] {
+        self.initial_squares
+    }
+
+    /// 初期配置前に disconnect 処理される山札インデックス (0-indexed で取り出される順) の組たちを返す。
+    /// 処理は先頭から順に行われる。
+    pub const fn disconnect_pairs(&self) -> &'static [(usize, usize)] {
+        self.disconnect_pairs
+    }
+
+    /// レベルの終了までにかかる手数 (初期配置以外の全てのカードを置き終えるまで) を返す。
+    pub const fn ply_count(&self) -> usize {
+        Card::NUM - self.initial_squares.len()
+    }
+
+    /// 原作で確認済みのレベルクリアに必要な所持金を返す。未確認 (レベル 8 以下) ならば `None` を返す。
+    pub const fn money_required(&self) -> Option<Money> {
+        self.money_required
+    }
+
+    /// レベルクリアに盤面を空にする必要があるかどうかを返す。
+    pub const fn must_clear_board(&self) -> bool {
+        self.must_clear_board
+    }
+
+    /// 必要所持金を `money_required` とするクリア条件を返す。
+    ///
+    /// `money_required` が `None` ならば原作で確認済みの値を用いる。それも未確認ならば `None` を返す。
+    pub const fn clear_condition(&self, money_required: Option<Money>) -> Option<ClearCondition> {
+        let money_required = match money_required {
+            Some(money_required) => money_required,
+            None => match self.money_required {
+                Some(money_required) => money_required,
+                None => return None,
+            },
+        };
+
+        Some(ClearCondition::new(money_required, self.must_clear_board))
+    }
+}
+
+/// レベルのクリア条件。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClearCondition {
+    money_required: Money,
+    must_clear_board: bool,
+}
+
+impl ClearCondition {
+    /// 必要所持金 `money_required` と盤面を空にする必要があるかどうか `must_clear_board` からクリア条件を作る。
+    pub const fn new(money_required: Money, must_clear_board: bool) -> Self {
+        Self {
+            money_required,
+            must_clear_board,
+        }
+    }
+
+    /// 必要所持金を `money_required` に置き換えたクリア条件を返す。
+    pub const fn with_money_required(self, money_required: Money) -> Self {
+        Self {
+            money_required,
+            ..self
+        }
+    }
+
+    /// レベルクリアに必要な所持金を返す。
+    pub const fn money_required(&self) -> Money {
+        self.money_required
+    }
+
+    /// レベルクリアに盤面を空にする必要があるかどうかを返す。
+    pub const fn must_clear_board(&self) -> bool {
+        self.must_clear_board
+    }
+
+    /// 全ての手を終えた時点の所持金と盤面がクリア条件を満たすかどうかを返す。
+    pub fn is_cleared(&self, money: Money, board: &Board) -> bool {
+        money >= self.money_required && (!self.must_clear_board || board.card_count() == 0)
+    }
+}
@@ -1,31 +1,53 @@
 mod board;
+mod bound;
 mod card;
 mod endgame;
+mod evaluator;
+mod frame_fit;
+mod frame_model;
+mod game;
 mod level;
 mod macros;
+mod manip;
 mod midgame;
+mod movie;
+mod pareto;
 mod position;
 mod solution;
 mod square;
 mod state;
+mod tuning;
 mod yaku;
 
 pub use self::board::*;
+pub use self::bound::*;
 pub use self::card::*;
 pub use self::endgame::*;
+pub use self::evaluator::*;
+pub use self::frame_fit::*;
+pub use self::frame_model::*;
+pub use self::game::*;
 pub use self::level::*;
+pub use self::manip::*;
 pub use self::midgame::*;
+pub use self::movie::*;
 pub use self::position::*;
 pub use self::solution::*;
 pub use self::square::*;
 pub use self::state::*;
+pub use self::tuning::*;
 pub use self::yaku::*;
 
-/// フレーム数。
+/// フレーム数 (1 レベル分)。
 pub type Frame = u16;
 
+/// フレーム数 (複数レベル分)。`Frame` ではゲーム全体のフレーム数を表せないので別に用意する。
+pub type TotalFrame = u32;
+
 /// 金額 (10 ドル単位)。
 pub type Money = u16;
 
-/// レベル 9 または 10 の終了までにかかる手数 (レベル 8 以下はサポートしない)。
-pub const PLY_COUNT_MAX: usize = 45;
+/// 1 レベルの終了までにかかる手数の最大値 (初期配置のないレベル 1..=4 の手数)。
+///
+/// 各レベルの手数は [`LevelRules::ply_count`] を参照。
+pub const PLY_COUNT_MAX: usize = 52;
@@ -0,0 +1,206 @@
+//! 乱数調整 (レベル開始の遅延フレーム数) の候補のランク付け。
+//!
+//! 遅延フレーム数から初期山札を得る方法は [`DeckModel`] として差し替えられる。
+//! NOTE: 原作の山札生成ルーチンは未解析なので (`CardPile::parse_memory_initial` を参照)、山札の生成は行わない。
+//! 現状ではエミュレータ上で遅延ごとに採取した山札配列メモリダンプを引く [`CapturedDeckModel`] のみを提供するので、
+//! 調べたい遅延の個数だけメモリダンプを採取する必要がある。
+
+use rayon::prelude::*;
+
+use crate::evaluator::Evaluator;
+use crate::frame_model::FrameModel;
+use crate::level::{ClearCondition, Level};
+use crate::midgame::solve_midgame;
+use crate::position::CardPile;
+use crate::state::State;
+use crate::{Frame, Money, TotalFrame};
+
+/// レベル開始の遅延フレーム数から初期山札を得るモデル。
+///
+/// 遅延ごとの探索は複数スレッドで行われるため `Sync` を要求する。
+pub trait DeckModel: Sync {
+    /// 扱える遅延フレーム数の最大値を返す。
+    fn delay_max(&self) -> Frame;
+
+    /// レベル開始を `delay` フレーム遅らせたときの初期山札を返す。`delay <= self.delay_max()` でなければならない。
+    fn pile(&self, delay: Frame) -> CardPile;
+}
+
+/// 遅延フレーム数ごとに採取した初期山札を引くモデル。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapturedDeckModel {
+    piles: Vec<CardPile>,
+}
+
+impl CapturedDeckModel {
+    /// `piles[d]` をレベル開始を `d` フレーム遅らせたときの初期山札とするモデルを作る。
+    pub fn new(piles: Vec<CardPile>) -> Self {
+        assert!(!piles.is_empty());
+        assert!(
+            piles.len() <= usize::from(Frame::MAX) + 1,
+            "遅延フレーム数が大きすぎる"
+        );
+
+        Self { piles }
+    }
+}
+
+impl DeckModel for CapturedDeckModel {
+    fn delay_max(&self) -> Frame {
+        (self.piles.len() - 1) as Frame
+    }
+
+    fn pile(&self, delay: Frame) -> CardPile {
+        self.piles[usize::from(delay)].clone()
+    }
+}
+
+/// レベル開始の遅延フレーム数の評価結果。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DelayCandidate {
+    delay: Frame,
+    state: Option<State>,
+}
+
+impl DelayCandidate {
+    /// レベル開始の遅延フレーム数を返す。
+    pub fn delay(&self) -> Frame {
+        self.delay
+    }
+
+    /// 簡易探索で見つかった最速のクリア状態を返す。見つからなかった場合は `None` を返す。
+    pub fn state(&self) -> Option<&State> {
+        self.state.as_ref()
+    }
+
+    /// 遅延込みの推定最終フレーム数を返す。クリア状態が見つからなかった場合は `None` を返す。
+    pub fn frame_estimate(&self) -> Option<TotalFrame> {
+        self.state
+            .as_ref()
+            .map(|state| TotalFrame::from(self.delay) + TotalFrame::from(state.frame()))
+    }
+}
+
+/// 遅延フレーム数 `0..=delay_max` のそれぞれについて簡易探索を行い、遅延込みの推定最終フレーム数の小さい順に並べて返す。
+///
+/// 各遅延フレーム数に対する初期山札は `deck` から得る。各遅延の簡易探索は並列に行う。
+/// 簡易探索は `beam_width` の `solve_midgame` (同一盤面ではフレーム数最小の状態のみを残す) でレベルを最後まで探索し、
+/// クリア条件 `cond` を満たす最速の状態を採る。
+/// クリア状態が見つからなかった遅延は末尾に (遅延の小さい順で) 並ぶ。
+/// フレームコストは `model` に、中盤探索の状態の評価は `evaluator` に従う。
+#[allow(clippy::too_many_arguments)]
+pub fn rank_delays<D: DeckModel + ?Sized, M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    money: Money,
+    deck: &D,
+    delay_max: Frame,
+    beam_width: usize,
+    rng_seed: u64,
+    model: &M,
+    evaluator: &dyn Evaluator,
+) -> Vec<DelayCandidate> {
+    assert!(delay_max <= deck.delay_max());
+
+    let ply_count = level.rules().ply_count();
+
+    let mut cands: Vec<_> = (0..=delay_max)
+        .into_par_iter()
+        .map(|delay| {
+            let (states, _) = solve_midgame(
+                level,
+                money,
+                deck.pile(delay),
+                ply_count,
+                beam_width,
+                1,
+                rng_seed,
+                model,
+                evaluator,
+            );
+            let state = states
+                .into_iter()
+                .filter(|state| cond.is_cleared(state.money(), state.board()))
+                .min_by_key(State::frame);
+            DelayCandidate { delay, state }
+        })
+        .collect();
+
+    cands.sort_by_key(|cand| {
+        (
+            cand.frame_estimate().is_none(),
+            cand.frame_estimate(),
+            cand.delay,
+        )
+    });
+
+    cands
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::card::Card;
+    use crate::evaluator::ParamEvaluator;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+
+    use super::*;
+
+    /// 遅延フレーム数をシードとして山札をシャッフルするモデル。
+    struct ShuffleDeckModel;
+
+    impl DeckModel for ShuffleDeckModel {
+        fn delay_max(&self) -> Frame {
+            Frame::MAX
+        }
+
+        fn pile(&self, delay: Frame) -> CardPile {
+            let mut cards = Card::all();
+            cards.shuffle(&mut SmallRng::seed_from_u64(u64::from(delay)));
+            CardPile::new_initial(cards)
+        }
+    }
+
+    #[test]
+    fn test_rank_delays() {
+        let model = LinearFrameModel::DEFAULT;
+        // 小さいビーム幅でも解が見つかるよう、盤面を空にしなくてよいことにする。
+        let cond = ClearCondition::new(0, false);
+
+        let cands = rank_delays(
+            LEVEL_9,
+            cond,
+            0,
+            &ShuffleDeckModel,
+            2,
+            50,
+            42,
+            &model,
+            &ParamEvaluator::default(),
+        );
+
+        let mut delays: Vec<_> = cands.iter().map(DelayCandidate::delay).collect();
+        delays.sort_unstable();
+        assert_eq!(delays, [0, 1, 2]);
+
+        assert!(cands
+            .windows(2)
+            .all(|w| w[0].frame_estimate() <= w[1].frame_estimate()));
+
+        // 推定最終フレーム数は、その遅延の山札で見つかった解のフレーム数に遅延を加えたもの。
+        for cand in &cands {
+            let state = cand.state().unwrap();
+            let records = state
+                .solution()
+                .replay(LEVEL_9, 0, ShuffleDeckModel.pile(cand.delay()), &model)
+                .unwrap();
+            assert_eq!(records.last().unwrap().frame_elapsed(), state.frame());
+            assert_eq!(
+                cand.frame_estimate(),
+                Some(TotalFrame::from(cand.delay()) + TotalFrame::from(state.frame()))
+            );
+        }
+    }
+}
@@ -1,38 +1,64 @@
 //! 中盤終わりまでの探索。
 
-use std::cmp::Reverse;
+use std::cmp::{Ordering, Reverse};
 
+use arrayvec::ArrayVec;
 use ordered_float::NotNan;
 use rand::prelude::*;
+use rayon::prelude::*;
 
-use crate::level::{Level, LEVEL_10, LEVEL_9};
+use crate::board::PackedBoard;
+use crate::bound::can_clear_board;
+use crate::card::Card;
+use crate::evaluator::Evaluator;
+use crate::frame_model::FrameModel;
+use crate::level::Level;
+use crate::pareto::thin_indices;
 use crate::position::CardPile;
+use crate::solution::Solution;
+use crate::square::{Col, COL_A};
 use crate::state::State;
-use crate::{Money, PLY_COUNT_MAX};
+use crate::yaku::process_yaku_chain;
+use crate::{Frame, Money};
 
+/// ビーム幅の上限。
+///
+/// ピークメモリ使用量は展開後の状態たち (1 状態 32 バイトの [`Node`]) と選別用のキー (1 状態 16 バイト) でほぼ決まり、
+/// ビーム幅をこの上限としてレベル 9 の 44 手を探索した実測値は約 2.6 GB (1 コアで約 16 分) だった。
 const BEAM_WIDTH_MAX: usize = 10_000_000;
 
+/// 評価時に同一の乱数生成器を用いる状態の個数。
+///
+/// スレッド数によらず結果を一致させるため、乱数生成器はスレッドごとではなくこの単位で用意する。
+const EVAL_CHUNK_LEN: usize = 1 << 12;
+
 /// 中盤終わりまでの探索 (`ply_count` 手) を行い、(有望と思われる状態集合, 残りの山札) を返す。
 ///
 /// 返される状態集合はスコアの良い順にソートされている。
-pub fn solve_midgame(
+/// 各手の後、同一盤面の状態たちは (フレーム数, 所持金) についてパレート最適なもののみを残す。
+/// その個数が `pareto_len_max` を超える場合は両端を含めて等間隔に間引く (1 ならフレーム数最小のもののみ)。
+/// 盤面を空にする必要があるレベルで [`Evaluator::prune_unclearable`] が真ならば、[`can_clear_board`] が偽となる状態は途中で捨てられる。
+/// フレームコストは `model` に、状態の評価は `evaluator` に従う。
+///
+/// メモリ節約のため、探索中の状態は詰めた盤面と (親の番号, 着手) のみを持ち、
+/// 手順は最後まで残った状態についてのみ復元する。
+///
+/// 探索は rayon のスレッドプール上で並列に行われる。結果は `rng_seed` のみで決まり、スレッド数には依存しない。
+#[allow(clippy::too_many_arguments)]
+pub fn solve_midgame<M: FrameModel + ?Sized>(
     level: Level,
     money: Money,
     pile: CardPile,
     ply_count: usize,
     beam_width: usize,
+    pareto_len_max: usize,
     rng_seed: u64,
+    model: &M,
+    evaluator: &dyn Evaluator,
 ) -> (Vec<State>, CardPile) {
-    assert!(ply_count <= PLY_COUNT_MAX);
+    assert!(ply_count <= level.rules().ply_count());
     assert!(beam_width <= BEAM_WIDTH_MAX);
-
-    let mut rng = SmallRng::seed_from_u64(rng_seed);
-
-    let f_eval = match level {
-        LEVEL_9 => eval_level9,
-        LEVEL_10 => eval_level10,
-        _ => panic!("レベル 8 以下は未サポート"),
-    };
+    assert!(pareto_len_max > 0);
 
     let (state_ini, mut pile) = State::new_initial(level, money, pile);
 
@@ -40,84 +66,580 @@ pub fn solve_midgame(
     eprintln!("{state_ini}");
     eprintln!();
 
-    let mut beam = Vec::<State>::with_capacity(beam_width);
-    beam.push(state_ini);
+    let prune_unclearable = level.rules().must_clear_board() && evaluator.prune_unclearable();
 
-    let mut beam_nxt = Vec::<State>::with_capacity(5 * beam_width);
+    let mut beam = vec![Node::new_root(&state_ini)];
+    let mut arena = MoveArena::new();
 
     for ply in 0..ply_count {
-        eprintln!("midgame ply={ply}");
+        eprintln!("midgame ply={ply} arena={}", arena.len());
 
         let card = pile.pop().unwrap();
 
-        for state in beam.drain(..) {
-            beam_nxt.extend(state.neighbors(ply, card));
-        }
+        // 盤面を空にできる見込みがない状態は展開時に捨てる。
+        let mut beam_nxt: Vec<Node> = beam
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(idx, node)| {
+                node.children(idx, card, model, |state| {
+                    !prune_unclearable || can_clear_board(state, &pile)
+                })
+            })
+            .collect();
 
-        // beam_nxt 内に盤面の重複がある場合、フレームコストが最小のもののみを残す。
-        beam_nxt.sort_unstable_by(|lhs, rhs| {
-            (lhs.board(), lhs.frame()).cmp(&(rhs.board(), rhs.frame()))
-        });
-        beam_nxt.dedup_by(|a, b| a.board() == b.board());
+        // beam_nxt 内に盤面の重複がある場合、(フレーム数, 所持金) についてパレート最適なもののみを残す。
+        // 盤面のハッシュ値でバケットに分けてから各バケット内をソートすれば、同一盤面の状態たちは連続する。
+        let bucket_bits = bucket_bits(beam_nxt.len());
+        partition_by_bucket(&mut beam_nxt, bucket_bits);
+        beam_nxt
+            .par_chunk_by_mut(|lhs, rhs| lhs.bucket(bucket_bits) == rhs.bucket(bucket_bits))
+            .for_each(|bucket| {
+                bucket.sort_unstable_by(Node::cmp_total);
+            });
+        beam_nxt.dedup_by(|node, kept| kept.dominates(node));
+        beam_nxt = thin_pareto_fronts(beam_nxt, pareto_len_max);
 
         // beam_nxt をスコア上位 beam_width 件に絞る。
-        if beam_nxt.len() > beam_width {
-            beam_nxt.select_nth_unstable_by_key(beam_width, |state| {
-                Reverse(f_eval(&mut rng, ply, state))
+        beam = if beam_nxt.len() > beam_width {
+            select_best(beam_nxt, beam_width, |chunk_idx, nodes| {
+                let mut rng = chunk_rng(rng_seed, ply, chunk_idx);
+                nodes
+                    .iter()
+                    .map(|node| {
+                        let state = node.to_state();
+                        NotNan::new(evaluator.eval(&mut rng, level, ply, &state)).unwrap()
+                    })
+                    .collect()
+            })
+        } else {
+            beam_nxt
+        };
+
+        arena.push_layer(&beam);
+    }
+
+    let mut states: Vec<State> = beam
+        .par_iter()
+        .enumerate()
+        .map(|(idx, node)| {
+            State::new(
+                node.frame,
+                node.money,
+                node.board.unpack(),
+                arena.solution(state_ini.solution(), idx),
+            )
+        })
+        .collect();
+
+    states.sort_unstable_by_key(|state| Reverse(state.money()));
+
+    (states, pile)
+}
+
+/// 中盤探索中の状態。
+///
+/// メモリ節約のため、盤面は詰めて持ち、手順の代わりに直前のビームにおける親の番号と親からの着手のみを持つ。
+/// 大きさは 32 バイトで、ビーム幅を大きくしたときのメモリ使用量はほぼこれで決まる。
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Node {
+    board: PackedBoard,
+    /// 盤面の Zobrist ハッシュ値の上位 32 bit。重複除去のバケット分けに用いる。
+    hash: u32,
+    frame: Frame,
+    money: Money,
+    parent: u32,
+    col: Col,
+}
+
+impl Node {
+    /// 探索の根を作る。根の (親の番号, 着手) は参照されない。
+    fn new_root(state: &State) -> Self {
+        Self {
+            board: state.board().pack(),
+            hash: (state.board().zobrist_hash() >> 32) as u32,
+            frame: state.frame(),
+            money: state.money(),
+            parent: 0,
+            col: COL_A,
+        }
+    }
+
+    /// 手順を空とした状態を返す。
+    fn to_state(&self) -> State {
+        State::new(self.frame, self.money, self.board.unpack(), Solution::new())
+    }
+
+    /// 盤面を第一キーとする全順序で比較する。ソート結果がソートアルゴリズムに依存しないようにするためのもの。
+    fn cmp_total(&self, other: &Self) -> Ordering {
+        (
+            &self.board,
+            self.frame,
+            Reverse(self.money),
+            self.parent,
+            self.col,
+        )
+            .cmp(&(
+                &other.board,
+                other.frame,
+                Reverse(other.money),
+                other.parent,
+                other.col,
+            ))
+    }
+
+    /// 重複除去のバケット数が `1 << bits` のとき、`self` が属するバケットの番号を返す。
+    fn bucket(&self, bits: u32) -> usize {
+        self.hash.checked_shr(u32::BITS - bits).unwrap_or(0) as usize
+    }
+
+    /// `self` が `other` 以上に良い状態かどうかを返す。[`State::dominates`] と同様。
+    fn dominates(&self, other: &Self) -> bool {
+        self.board == other.board && self.frame <= other.frame && self.money >= other.money
+    }
+
+    /// ビームの `idx` 番目の状態 `self` において、ツモを `card` としたときの子たちのうち
+    /// `f_keep` が真となるものを列挙する (`f_keep` に渡される状態の手順は空)。
+    /// フレームコストは `model` に従う。
+    fn children<M, F>(&self, idx: usize, card: Card, model: &M, f_keep: F) -> ArrayVec<Self, 5>
+    where
+        M: FrameModel + ?Sized,
+        F: Fn(&State) -> bool,
+    {
+        let board = self.board.unpack();
+        let mut res = ArrayVec::<Self, 5>::new();
+
+        for col in Col::all() {
+            let Some((mut board, row)) = board.put(col, card) else {
+                continue;
+            };
+            let frame_put = model.put(row);
+            let (frame_yaku, prize) = process_yaku_chain(&mut board, model);
+            let state = State::new(
+                self.frame + frame_put + frame_yaku,
+                self.money + prize,
+                board,
+                Solution::new(),
+            );
+            if !f_keep(&state) {
+                continue;
+            }
+            res.push(Self {
+                board: state.board().pack(),
+                hash: (state.board().zobrist_hash() >> 32) as u32,
+                frame: state.frame(),
+                money: state.money(),
+                parent: idx as u32,
+                col,
             });
-            beam_nxt.truncate(beam_width);
         }
 
-        beam.append(&mut beam_nxt);
+        res
+    }
+}
+
+/// 各手の後のビームの状態たちの (親の番号, 着手) を記録し、手順を復元できるようにする。
+///
+/// 最新のビームから辿れなくなった記録は随時捨てるので、手数 * ビーム幅 よりはずっと小さく収まる。
+#[derive(Debug)]
+struct MoveArena {
+    /// `layers[ply]`: `ply` 手目の後のビームの各状態の (親の番号, 着手)。
+    /// ただし `ply` が最新でない層は、次の層から参照されているものだけが詰めて残されている。
+    layers: Vec<Vec<(u32, Col)>>,
+}
+
+impl MoveArena {
+    fn new() -> Self {
+        Self { layers: vec![] }
     }
 
-    beam.sort_unstable_by_key(|state| Reverse(state.money()));
+    /// 記録の総数を返す。
+    fn len(&self) -> usize {
+        self.layers.iter().map(Vec::len).sum()
+    }
+
+    /// 最新のビーム `beam` の記録を追加し、辿れなくなった記録を捨てる。
+    fn push_layer(&mut self, beam: &[Node]) {
+        self.layers
+            .push(beam.iter().map(|node| (node.parent, node.col)).collect());
+
+        // 上の層から順に、次の層から参照されていない記録を捨てて番号を振り直す。
+        // ある層で何も捨てなければ、それより下の層は前回から変わらないので打ち切れる。
+        for ply in (0..self.layers.len() - 1).rev() {
+            let (lower, upper) = self.layers.split_at_mut(ply + 1);
+            let (layer, layer_nxt) = (&mut lower[ply], &mut upper[0]);
+
+            let mut remap = vec![u32::MAX; layer.len()];
+            for &(parent, _) in layer_nxt.iter() {
+                remap[parent as usize] = 0;
+            }
+            if remap.iter().all(|&idx| idx == 0) {
+                break;
+            }
 
-    (beam, pile)
+            let mut len = 0;
+            for idx in 0..layer.len() {
+                if remap[idx] == 0 {
+                    remap[idx] = len as u32;
+                    layer[len] = layer[idx];
+                    len += 1;
+                }
+            }
+            layer.truncate(len);
+            layer.shrink_to_fit();
+
+            for (parent, _) in layer_nxt.iter_mut() {
+                *parent = remap[*parent as usize];
+            }
+        }
+    }
+
+    /// 根の手順 `solution_root` に、最新のビームの `idx` 番目の状態に至る手順を付け足したものを返す。
+    fn solution(&self, solution_root: &Solution, mut idx: usize) -> Solution {
+        let mut solution = solution_root.clone();
+
+        for (ply, layer) in self.layers.iter().enumerate().rev() {
+            let (parent, col) = layer[idx];
+            solution.add_move_inplace(ply, col);
+            idx = parent as usize;
+        }
+
+        solution
+    }
 }
 
-/// レベル 9 用の評価関数。
-fn eval_level9(rng: &mut SmallRng, ply: usize, state: &State) -> NotNan<f64> {
-    // 所持金は特に意識しなくても足りるっぽい。
-
-    let frame = f64::from(state.frame());
-    //let money = f64::from(state.money());
-    let card_count = f64::from(state.card_count());
-
-    //let value_money = 2200.0 * (1.0 / (1.0 + (-0.0015 * money).exp()) - 0.5);
-
-    /*
-    let value_frame = -frame;
-    let value_card_count = match ply {
-        0..=30 => 0.0,
-        31..=37 => -50.0 * card_count,
-        38.. => -100.0 * card_count,
-        _ => unreachable!(),
-    };
-    let value = value_frame + value_card_count;
-    */
-
-    let value_frame = -frame;
-    let value_card_count = match ply {
-        0..=30 => 0.0,
-        31.. => -50.0 * card_count,
-        _ => unreachable!(),
-    };
-    let value_rand = match ply {
-        0..=20 => rng.gen_range(0.0..300.0),
-        21..=30 => rng.gen_range(0.0..200.0),
-        31..=35 => rng.gen_range(0.0..100.0),
-        36.. => rng.gen_range(0.0..50.0),
-        _ => unreachable!(),
-    };
-    let value = value_frame + value_card_count + value_rand;
-
-    NotNan::new(value).unwrap()
+/// 重複除去のバケット数の 2 を底とする対数を、状態数 `len` に対して 1 バケットあたり十数個となるよう選ぶ。
+fn bucket_bits(len: usize) -> u32 {
+    const BUCKET_LEN_MEAN: usize = 16;
+
+    (len / BUCKET_LEN_MEAN).max(1).ilog2()
 }
 
-/// レベル 10 用の評価関数。
-fn eval_level10(rng: &mut SmallRng, ply: usize, state: &State) -> NotNan<f64> {
-    // 所持金は足りてるので特に変える必要なかった。
+/// `nodes` をバケット番号の昇順に並べ替える。in-place 処理で、計算量は `O(nodes.len())`。
+/// 同一バケット内の順序は不定だが、入力のみで決まる。
+///
+/// バケットが多いと書き込み先が散らばって遅いので、まずバケット番号の上位 `TOP_BITS` bit で分け、
+/// 続いて各区間を残りの bit で並列に分ける。1 段目のヒストグラムも並列に数える。
+fn partition_by_bucket(nodes: &mut [Node], bits: u32) {
+    const TOP_BITS: u32 = 8;
+
+    let bits_top = bits.min(TOP_BITS);
+    let counts = nodes
+        .par_iter()
+        .fold(
+            || vec![0; 1 << bits_top],
+            |mut counts, node| {
+                counts[node.bucket(bits_top)] += 1;
+                counts
+            },
+        )
+        .reduce(
+            || vec![0; 1 << bits_top],
+            |mut counts, counts_other| {
+                for (count, count_other) in counts.iter_mut().zip(counts_other) {
+                    *count += count_other;
+                }
+                counts
+            },
+        );
+    partition_by_key(nodes, &counts, |node| node.bucket(bits_top));
 
-    eval_level9(rng, ply, state)
+    // 上位 bit が等しい区間内では、バケット番号の下位 bit のみで分ければよい。
+    let mask = (1 << (bits - bits_top)) - 1;
+    let mut slices = Vec::with_capacity(counts.len());
+    let mut rest = nodes;
+    for &count in &counts {
+        let (slice, tail) = rest.split_at_mut(count);
+        slices.push(slice);
+        rest = tail;
+    }
+    slices.into_par_iter().for_each(|slice| {
+        let mut counts = vec![0; mask + 1];
+        for node in slice.iter() {
+            counts[node.bucket(bits) & mask] += 1;
+        }
+        partition_by_key(slice, &counts, |node| node.bucket(bits) & mask);
+    });
+}
+
+/// キー `f_key` の値が `k` のものが `counts[k]` 個ずつある `nodes` を、キーの昇順に並べ替える (American flag sort)。
+fn partition_by_key(nodes: &mut [Node], counts: &[usize], f_key: impl Fn(&Node) -> usize) {
+    // ends[k]: キー k の区間の終端。nexts[k]: キー k の区間で次に埋める位置。
+    let mut ends = Vec::<usize>::with_capacity(counts.len());
+    let mut nexts = Vec::<usize>::with_capacity(counts.len());
+    let mut sum = 0;
+    for &count in counts {
+        nexts.push(sum);
+        sum += count;
+        ends.push(sum);
+    }
+
+    for k in 0..ends.len() {
+        while nexts[k] < ends[k] {
+            let c = f_key(&nodes[nexts[k]]);
+            if c != k {
+                nodes.swap(nexts[k], nexts[c]);
+            }
+            nexts[c] += 1;
+        }
+    }
+}
+
+/// 盤面ごとに (フレーム数昇順で) 連続して並んだパレートフロントたち `nodes` を、それぞれ `len_max` 個以下に間引く。
+///
+/// 間引く場合は両端 (フレーム数最小のものと所持金最大のもの) を含めて等間隔に選ぶ。
+/// `len_max == 1` ならフレーム数最小のもののみを残す。
+fn thin_pareto_fronts(nodes: Vec<Node>, len_max: usize) -> Vec<Node> {
+    let mut keep = Vec::<bool>::with_capacity(nodes.len());
+    for front in nodes.chunk_by(|lhs, rhs| lhs.board == rhs.board) {
+        let start = keep.len();
+        keep.extend(std::iter::repeat_n(false, front.len()));
+        for i in thin_indices(front.len(), len_max) {
+            keep[start + i] = true;
+        }
+    }
+
+    nodes
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(node, keep)| keep.then_some(node))
+        .collect()
+}
+
+/// `states` をスコア上位 `count` 件に絞る。残った状態の順序は元の順序を保つ。
+///
+/// `f_score` は `EVAL_CHUNK_LEN` 個ずつに区切った (チャンク番号, 状態たち) を受け取り、各状態のスコアを返す。
+/// スコアが等しい場合は元の順序で先にあるものを優先する。
+fn select_best<T, F>(states: Vec<T>, count: usize, f_score: F) -> Vec<T>
+where
+    T: Send + Sync,
+    F: Fn(usize, &[T]) -> Vec<NotNan<f64>> + Sync,
+{
+    let mut keys: Vec<(Reverse<NotNan<f64>>, usize)> = states
+        .par_chunks(EVAL_CHUNK_LEN)
+        .enumerate()
+        .flat_map_iter(|(chunk_idx, chunk)| {
+            let start = EVAL_CHUNK_LEN * chunk_idx;
+            f_score(chunk_idx, chunk)
+                .into_iter()
+                .enumerate()
+                .map(move |(i, score)| (Reverse(score), start + i))
+        })
+        .collect();
+
+    keys.par_sort_unstable();
+
+    let mut keep = vec![false; states.len()];
+    for &(_, idx) in &keys[..count.min(keys.len())] {
+        keep[idx] = true;
+    }
+
+    states
+        .into_par_iter()
+        .zip(keep)
+        .filter_map(|(state, keep)| keep.then_some(state))
+        .collect()
+}
+
+/// 評価の `chunk_idx` 番目のチャンクで用いる乱数生成器を返す。
+fn chunk_rng(rng_seed: u64, ply: usize, chunk_idx: usize) -> SmallRng {
+    let seed = rng_seed
+        ^ (ply as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (chunk_idx as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+
+    SmallRng::seed_from_u64(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::board::Board;
+    use crate::card::Card;
+    use crate::evaluator::ParamEvaluator;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+
+    use super::*;
+
+    #[test]
+    fn test_node_size() {
+        assert_eq!(std::mem::size_of::<Node>(), 32);
+    }
+
+    #[test]
+    fn test_solve_midgame_deterministic() {
+        let solve = |num_threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                solve_midgame(
+                    LEVEL_9,
+                    0,
+                    CardPile::new_initial(Card::all()),
+                    12,
+                    1000,
+                    3,
+                    42,
+                    &LinearFrameModel::DEFAULT,
+                    &ParamEvaluator::default(),
+                )
+                .0
+            })
+        };
+
+        let states = solve(1);
+        assert!(!states.is_empty());
+        assert_eq!(solve(4), states);
+    }
+
+    #[test]
+    fn test_solve_midgame_solution() {
+        let pile = CardPile::new_initial(Card::all());
+        let (states, _) = solve_midgame(
+            LEVEL_9,
+            0,
+            pile.clone(),
+            20,
+            200,
+            2,
+            42,
+            &LinearFrameModel::DEFAULT,
+            &ParamEvaluator::default(),
+        );
+        assert!(!states.is_empty());
+
+        // 復元された手順を再生すると同じ状態に至る。
+        for state in states {
+            let records = state
+                .solution()
+                .replay(LEVEL_9, 0, pile.clone(), &LinearFrameModel::DEFAULT)
+                .unwrap();
+            assert_eq!(records.len(), 20);
+            let last = records.last().unwrap();
+            assert_eq!(last.board_after(), state.board());
+            assert_eq!(last.frame_elapsed(), state.frame());
+            assert_eq!(last.money(), state.money());
+        }
+    }
+
+    #[test]
+    fn test_solve_midgame_prune_unclearable() {
+        /// 盤面のカードが少ないほど良いとする評価関数。
+        /// 終盤では残り 1, 2 枚となって盤面を空にできない状態が上位に来やすい。
+        struct EmptyBoardEvaluator(bool);
+
+        impl Evaluator for EmptyBoardEvaluator {
+            fn eval(&self, _rng: &mut SmallRng, _level: Level, _ply: usize, state: &State) -> f64 {
+                -f64::from(state.card_count())
+            }
+
+            fn prune_unclearable(&self) -> bool {
+                self.0
+            }
+        }
+
+        let solve = |prune_unclearable: bool| {
+            solve_midgame(
+                LEVEL_9,
+                0,
+                CardPile::new_initial(Card::all()),
+                44,
+                100,
+                1,
+                42,
+                &LinearFrameModel::DEFAULT,
+                &EmptyBoardEvaluator(prune_unclearable),
+            )
+        };
+
+        let (states, pile) = solve(true);
+        assert!(!states.is_empty());
+        assert!(states.iter().all(|state| can_clear_board(state, &pile)));
+
+        let (states, pile) = solve(false);
+        assert!(states.iter().any(|state| !can_clear_board(state, &pile)));
+    }
+
+    #[test]
+    fn test_partition_by_bucket() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        let nodes: Vec<Node> = (0..1000)
+            .map(|_| Node {
+                board: Board::new().pack(),
+                hash: rng.gen(),
+                frame: rng.gen(),
+                money: 0,
+                parent: 0,
+                col: COL_A,
+            })
+            .collect();
+        let key = |node: &Node| (node.hash, node.frame);
+        let mut nodes_sorted = nodes.clone();
+        nodes_sorted.sort_by_key(key);
+
+        assert_eq!(bucket_bits(nodes.len()), 5);
+        // 12 bit では 2 段に分けて並べ替える。
+        for bits in [0, 5, 12] {
+            let mut nodes = nodes.clone();
+            partition_by_bucket(&mut nodes, bits);
+            assert!(nodes
+                .windows(2)
+                .all(|w| w[0].bucket(bits) <= w[1].bucket(bits)));
+
+            nodes.sort_by_key(key);
+            assert_eq!(nodes, nodes_sorted);
+        }
+    }
+
+    #[test]
+    fn test_thin_pareto_fronts() {
+        let board_a: Board = indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            S2........
+        "}
+        .parse()
+        .unwrap();
+        let board_b: Board = indoc! {"
+            ..........
+            ..........
+            ..........
+            ..........
+            ..S2......
+        "}
+        .parse()
+        .unwrap();
+        let make_nodes = |board: &Board, front: &[(Frame, Money)]| -> Vec<Node> {
+            front
+                .iter()
+                .map(|&(frame, money)| Node {
+                    board: board.pack(),
+                    hash: (board.zobrist_hash() >> 32) as u32,
+                    frame,
+                    money,
+                    parent: 0,
+                    col: COL_A,
+                })
+                .collect()
+        };
+
+        let mut nodes = make_nodes(&board_a, &[(10, 5), (11, 5), (12, 8), (15, 9), (20, 20)]);
+        nodes.extend(make_nodes(&board_b, &[(10, 5)]));
+        nodes.dedup_by(|node, kept| kept.dominates(node));
+        assert_eq!(nodes.len(), 5);
+
+        let mut expected = make_nodes(&board_a, &[(10, 5), (12, 8), (20, 20)]);
+        expected.extend(make_nodes(&board_b, &[(10, 5)]));
+        assert_eq!(thin_pareto_fronts(nodes.clone(), 3), expected);
+
+        let mut expected = make_nodes(&board_a, &[(10, 5)]);
+        expected.extend(make_nodes(&board_b, &[(10, 5)]));
+        assert_eq!(thin_pareto_fronts(nodes.clone(), 1), expected);
+
+        assert_eq!(thin_pareto_fronts(nodes.clone(), usize::MAX), nodes);
+    }
 }
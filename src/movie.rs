@@ -0,0 +1,553 @@
+//! エミュレータ用入力ムービー (FCEUX .fm2 / BizHawk .bk2) の生成および読み込み。
+//!
+//! NOTE: 実験的な機能。生成したムービーは実機やエミュレータ上での再生による検証を経ていない。
+//! 以下の制約がある:
+//!
+//! * 入力タイミングのモデルは概算で、実際のムービーとの照合は未了。
+//! * ムービーはレベル開始時から始まるので、電源投入から再生できない。
+//!   レベル開始時までの入力を含む既存のムービーに継ぎ足して使う必要がある。
+//! * .fm2 のヘッダの `romChecksum` と `guid` はダミー値なので、再生前に書き換える必要がある。
+//!
+//! 入力タイミングは以下のモデルに従う:
+//!
+//! * 各手の開始時、ツモは `InputModel::col_start` の列上に出現する。
+//! * 手の開始から `InputModel::input_delay` フレーム後に入力受付が始まる。
+//! * 左右キーを 1F 押して 1F 離すごとにツモが 1 列移動する。
+//! * 目的の列に着いたら落下ボタンを 1F 押す。
+//! * 各手の所要フレーム数はシミュレータのフレームコストに等しく、次の手はその直後に始まる。
+//!
+//! よってムービーの長さはシミュレータ上のフレーム数と一致する。
+//!
+//! ムービーの読み込み時は、落下ボタンの押下開始フレームを着手とみなし、
+//! 直前の着手以降の左右キーの押下開始回数から列を求める。
+
+use std::io::{Seek, Write};
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+
+use crate::frame_model::FrameModel;
+use crate::level::Level;
+use crate::position::CardPile;
+use crate::solution::Solution;
+use crate::square::{Col, COL_C};
+use crate::state::State;
+use crate::{Frame, Money};
+
+/// NES コントローラのボタン入力 (1 フレーム分)。
+///
+/// ビット配置は NES コントローラの読み出し順 (A, B, Select, Start, Up, Down, Left, Right)。
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Buttons(u8);
+
+impl Buttons {
+    pub const NONE: Self = Self(0);
+    pub const A: Self = Self(1 << 0);
+    pub const B: Self = Self(1 << 1);
+    pub const SELECT: Self = Self(1 << 2);
+    pub const START: Self = Self(1 << 3);
+    pub const UP: Self = Self(1 << 4);
+    pub const DOWN: Self = Self(1 << 5);
+    pub const LEFT: Self = Self(1 << 6);
+    pub const RIGHT: Self = Self(1 << 7);
+
+    pub const fn from_inner(inner: u8) -> Self {
+        Self(inner)
+    }
+
+    pub const fn to_inner(self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// `self` が `other` のボタンを全て含むかどうかを返す。
+    pub const fn contains(self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// FCEUX の入力ログ形式 ("RLDUTSBA") の文字列をパースする。'.' と ' ' 以外の文字は押下とみなす。
+    fn parse_fm2(s: &str) -> anyhow::Result<Self> {
+        let chars: Vec<_> = s.chars().collect();
+        ensure!(
+            chars.len() == FM2_MNEMONIC.len(),
+            "入力文字列は {} 文字でなければならない: '{s}'",
+            FM2_MNEMONIC.len()
+        );
+
+        let buttons = std::iter::zip(FM2_MNEMONIC, chars)
+            .filter(|(_, c)| !matches!(c, '.' | ' '))
+            .fold(Self::NONE, |acc, (&(button, _), _)| acc.union(button));
+
+        Ok(buttons)
+    }
+
+    /// FCEUX の入力ログ形式 ("RLDUTSBA") でフォーマットする。
+    fn fmt_fm2(self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        Self::fmt_with_mnemonic(self, f, FM2_MNEMONIC)
+    }
+
+    /// BizHawk の入力ログ形式 ("UDLRSsBA") でフォーマットする。
+    fn fmt_bk2(self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        Self::fmt_with_mnemonic(self, f, BK2_MNEMONIC)
+    }
+
+    fn fmt_with_mnemonic(
+        self,
+        f: &mut impl std::fmt::Write,
+        mnemonic: &[(Self, char); 8],
+    ) -> std::fmt::Result {
+        for &(button, c) in mnemonic {
+            f.write_char(if self.contains(button) { c } else { '.' })?;
+        }
+
+        Ok(())
+    }
+}
+
+const FM2_MNEMONIC: &[(Buttons, char); 8] = &[
+    (Buttons::RIGHT, 'R'),
+    (Buttons::LEFT, 'L'),
+    (Buttons::DOWN, 'D'),
+    (Buttons::UP, 'U'),
+    (Buttons::START, 'T'),
+    (Buttons::SELECT, 'S'),
+    (Buttons::B, 'B'),
+    (Buttons::A, 'A'),
+];
+
+const BK2_MNEMONIC: &[(Buttons, char); 8] = &[
+    (Buttons::UP, 'U'),
+    (Buttons::DOWN, 'D'),
+    (Buttons::LEFT, 'L'),
+    (Buttons::RIGHT, 'R'),
+    (Buttons::START, 'S'),
+    (Buttons::SELECT, 's'),
+    (Buttons::B, 'B'),
+    (Buttons::A, 'A'),
+];
+
+/// 入力タイミングのモデル。詳細はモジュールのドキュメントを参照。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InputModel {
+    /// 各手の開始時にツモが出現する列。
+    pub col_start: Col,
+    /// 各手の開始から入力受付開始までのフレーム数。
+    pub input_delay: Frame,
+    /// 落下ボタン。
+    pub button_drop: Buttons,
+}
+
+impl Default for InputModel {
+    fn default() -> Self {
+        Self {
+            col_start: COL_C,
+            input_delay: 1,
+            button_drop: Buttons::A,
+        }
+    }
+}
+
+impl InputModel {
+    /// 手の開始時点からの相対フレームと入力の組たちを返す。
+    fn ply_inputs(&self, col: Col) -> Vec<(Frame, Buttons)> {
+        let (button_move, dist) = if col < self.col_start {
+            (Buttons::LEFT, self.col_start.to_inner() - col.to_inner())
+        } else {
+            (Buttons::RIGHT, col.to_inner() - self.col_start.to_inner())
+        };
+        let dist = Frame::from(dist);
+
+        let mut inputs: Vec<_> = (0..dist)
+            .map(|i| (self.input_delay + 2 * i, button_move))
+            .collect();
+        inputs.push((self.input_delay + 2 * dist, self.button_drop));
+
+        inputs
+    }
+}
+
+/// 入力ムービー (コントローラ 1 のみ)。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InputMovie(Vec<Buttons>);
+
+impl InputMovie {
+    pub fn new(inner: impl Into<Vec<Buttons>>) -> Self {
+        Self(inner.into())
+    }
+
+    /// レベル開始時から解手順 `solution` を入力するムービーを作る。
+    ///
+    /// 手順が不正な場合、またはある手の入力がその手の所要フレーム数に収まらない場合はエラーを返す。
+    pub fn from_solution<M: FrameModel + ?Sized>(
+        input_model: &InputModel,
+        level: Level,
+        money: Money,
+        pile: CardPile,
+        solution: &Solution,
+        model: &M,
+    ) -> anyhow::Result<Self> {
+        let records = solution.replay(level, money, pile, model)?;
+
+        let mut inner = Vec::<Buttons>::new();
+
+        for (ply, record) in records.iter().enumerate() {
+            let frame_ply = record.frame();
+
+            let start = inner.len();
+            inner.resize(start + usize::from(frame_ply), Buttons::NONE);
+            for (frame, buttons) in input_model.ply_inputs(record.col()) {
+                ensure!(
+                    frame < frame_ply,
+                    "ply {ply}: 入力 ({frame}F 目) が所要フレーム数 {frame_ply}F に収まらない"
+                );
+                inner[start + usize::from(frame)] = buttons;
+            }
+        }
+
+        Ok(Self(inner))
+    }
+
+    /// FCEUX の .fm2 形式の文字列をパースする。コントローラ 1 の入力のみを読み取る。
+    pub fn parse_fm2(s: impl AsRef<str>) -> anyhow::Result<Self> {
+        Self::_parse_fm2(s.as_ref())
+    }
+
+    fn _parse_fm2(s: &str) -> anyhow::Result<Self> {
+        let mut inner = Vec::<Buttons>::new();
+
+        for (i, line) in s.lines().enumerate() {
+            // 入力ログ行は '|' で始まる。それ以外はヘッダ行。
+            if !line.starts_with('|') {
+                continue;
+            }
+            // 行の形式: "|commands|port0|port1|port2|"
+            let port0 = line
+                .split('|')
+                .nth(2)
+                .ok_or_else(|| anyhow!("{} 行目: 無効な入力ログ行: '{line}'", i + 1))?;
+            let buttons = if port0.is_empty() {
+                Buttons::NONE
+            } else {
+                Buttons::parse_fm2(port0).with_context(|| format!("{} 行目", i + 1))?
+            };
+            inner.push(buttons);
+        }
+
+        Ok(Self(inner))
+    }
+
+    /// ムービーの `start_frame` フレーム目をレベル開始時とみなして着手を検出し、解手順を復元する。
+    ///
+    /// レベルの手数分の着手を検出した時点で打ち切る。
+    /// 復元した手順が不正な場合はエラーを返す。
+    pub fn analyze<M: FrameModel + ?Sized>(
+        &self,
+        input_model: &InputModel,
+        level: Level,
+        money: Money,
+        pile: CardPile,
+        start_frame: usize,
+        model: &M,
+    ) -> anyhow::Result<MovieAnalysis> {
+        let ply_count = level.rules().ply_count();
+
+        let (mut state, mut pile) = State::new_initial(level, money, pile);
+
+        let mut drop_frames = Vec::<usize>::with_capacity(ply_count);
+        let mut drop_frames_sim = Vec::<usize>::with_capacity(ply_count);
+
+        let mut col = input_model.col_start;
+        let mut prev = Buttons::NONE;
+        for (frame, buttons) in self.0.iter().copied().enumerate().skip(start_frame) {
+            if drop_frames.len() == ply_count {
+                break;
+            }
+
+            let pressed = Buttons(buttons.0 & !prev.0);
+            prev = buttons;
+
+            if pressed.contains(Buttons::LEFT) {
+                col = col.prev().unwrap_or(col);
+            }
+            if pressed.contains(Buttons::RIGHT) {
+                col = col.next().unwrap_or(col);
+            }
+            if !pressed.contains(input_model.button_drop) {
+                continue;
+            }
+
+            let ply = drop_frames.len();
+            let card = pile.pop().unwrap();
+            if state.board().put(col, card).is_none() {
+                bail!("ply {ply} (ムービー {frame} フレーム目): 列 {col} にカードを置けない");
+            }
+
+            // シミュレータ上での落下入力フレーム。
+            let offset_sim = input_model.ply_inputs(col).last().unwrap().0;
+            drop_frames_sim.push(usize::from(state.frame()) + usize::from(offset_sim));
+            drop_frames.push(frame - start_frame);
+
+            state = state.do_move(ply, card, col, model);
+            col = input_model.col_start;
+        }
+
+        Ok(MovieAnalysis {
+            state,
+            frame_movie: self.0.len().saturating_sub(start_frame),
+            drop_frames,
+            drop_frames_sim,
+        })
+    }
+
+    /// ムービーのフレーム数を返す。
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// ムービーが空かどうかを返す。
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 各フレームの入力を返す。
+    pub fn frames(&self) -> &[Buttons] {
+        &self.0
+    }
+
+    /// FCEUX の .fm2 形式で書き出す。
+    ///
+    /// ヘッダの `romChecksum` と `guid` はダミー値となる (モジュールのドキュメントを参照)。
+    pub fn write_fm2(&self, mut wtr: impl Write) -> anyhow::Result<()> {
+        wtr.write_all(FM2_HEADER.as_bytes())?;
+
+        let mut line = String::new();
+        for buttons in self.0.iter().copied() {
+            line.clear();
+            line.push_str("|0|");
+            buttons.fmt_fm2(&mut line)?;
+            line.push_str("|||\n");
+            wtr.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// BizHawk の .bk2 形式 (zip アーカイブ) で書き出す。
+    pub fn write_bk2(&self, wtr: impl Write + Seek) -> anyhow::Result<()> {
+        let mut zip = zip::ZipWriter::new(wtr);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+        zip.start_file("Header.txt", options)?;
+        zip.write_all(BK2_HEADER.as_bytes())?;
+
+        zip.start_file("Input Log.txt", options)?;
+        zip.write_all(self.input_log_bk2()?.as_bytes())?;
+
+        zip.finish()?;
+
+        Ok(())
+    }
+
+    fn input_log_bk2(&self) -> anyhow::Result<String> {
+        let mut log = String::new();
+
+        log.push_str("[Input]\n");
+        log.push_str(BK2_LOG_KEY);
+        log.push('\n');
+        for buttons in self.0.iter().copied() {
+            log.push_str("|..|");
+            buttons.fmt_bk2(&mut log)?;
+            log.push_str("|\n");
+        }
+        log.push_str("[/Input]\n");
+
+        Ok(log)
+    }
+}
+
+/// 入力ムービーから解手順を復元した結果。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MovieAnalysis {
+    state: State,
+    frame_movie: usize,
+    drop_frames: Vec<usize>,
+    drop_frames_sim: Vec<usize>,
+}
+
+impl MovieAnalysis {
+    /// 復元した手順を再生した後の状態を返す。
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// 復元した解手順を返す。
+    pub fn solution(&self) -> &Solution {
+        self.state.solution()
+    }
+
+    /// シミュレータ上のフレーム数を返す。
+    pub fn frame_sim(&self) -> Frame {
+        self.state.frame()
+    }
+
+    /// ムービー上のレベル開始時から末尾までのフレーム数を返す。
+    pub fn frame_movie(&self) -> usize {
+        self.frame_movie
+    }
+
+    /// 各手の落下入力のムービー上のフレーム (レベル開始時基準) を返す。
+    pub fn drop_frames(&self) -> &[usize] {
+        &self.drop_frames
+    }
+
+    /// 各手の落下入力のシミュレータ上のフレーム (レベル開始時基準) を返す。
+    pub fn drop_frames_sim(&self) -> &[usize] {
+        &self.drop_frames_sim
+    }
+}
+
+const FM2_HEADER: &str = "\
+version 3
+emuVersion 20604
+rerecordCount 0
+palFlag 0
+romFilename Cadillac
+romChecksum base64:AAAAAAAAAAAAAAAAAAAAAA==
+guid 00000000-0000-0000-0000-000000000000
+fourscore 0
+microphone 0
+port0 1
+port1 0
+port2 0
+FDS 0
+NewPPU 0
+";
+
+const BK2_HEADER: &str = "\
+MovieVersion BizHawk v2.0.0
+Platform NES
+GameName Cadillac
+Core NesHawk
+rerecordCount 0
+";
+
+const BK2_LOG_KEY: &str =
+    "LogKey:#Reset|Power|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|";
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
+
+    use super::*;
+
+    fn test_pile() -> CardPile {
+        CardPile::new_initial(Card::all())
+    }
+
+    /// 最初の 6 枚がどの 3 枚を並べても役にならない山札を返す。
+    fn pile_without_yaku() -> CardPile {
+        let firsts = [CARD_SA, CARD_C3, CARD_H5, CARD_D7, CARD_S9, CARD_CJ];
+        let mut inner: Vec<_> = Card::all()
+            .into_iter()
+            .filter(|card| !firsts.contains(card))
+            .collect();
+        inner.extend(firsts.into_iter().rev());
+
+        CardPile::new_initial(<[Card; 52]>::try_from(inner).unwrap())
+    }
+
+    #[test]
+    fn test_movie_io() {
+        let input_model = InputModel::default();
+        let model = LinearFrameModel::default();
+        let states = State::play_leftmost(LEVEL_9, 0, test_pile(), 20, &model);
+        let state = states.last().unwrap();
+        let solution = state.solution();
+
+        let movie =
+            InputMovie::from_solution(&input_model, LEVEL_9, 0, test_pile(), solution, &model)
+                .unwrap();
+        assert_eq!(movie.len(), usize::from(state.frame()));
+
+        let drop_count = movie
+            .frames()
+            .iter()
+            .filter(|buttons| buttons.contains(input_model.button_drop))
+            .count();
+        assert_eq!(drop_count, 20);
+
+        let mut fm2 = Vec::<u8>::new();
+        movie.write_fm2(&mut fm2).unwrap();
+        let fm2 = String::from_utf8(fm2).unwrap();
+        assert_eq!(
+            fm2.lines().filter(|line| line.starts_with('|')).count(),
+            movie.len()
+        );
+
+        let analysis = InputMovie::parse_fm2(&fm2)
+            .unwrap()
+            .analyze(&input_model, LEVEL_9, 0, test_pile(), 0, &model)
+            .unwrap();
+        assert_eq!(analysis.solution(), solution);
+        assert_eq!(usize::from(analysis.frame_sim()), analysis.frame_movie());
+        assert_eq!(analysis.drop_frames(), analysis.drop_frames_sim());
+
+        let mut bk2 = std::io::Cursor::new(Vec::<u8>::new());
+        movie.write_bk2(&mut bk2).unwrap();
+        assert!(!bk2.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_movie_from_solution_frames() {
+        let solution: Solution = "[A, E, C]".parse().unwrap();
+
+        let movie = InputMovie::from_solution(
+            &InputModel::default(),
+            LEVEL_1,
+            0,
+            pile_without_yaku(),
+            &solution,
+            &LinearFrameModel::DEFAULT,
+        )
+        .unwrap();
+
+        // 各手は最下段に置くので 37 + 16 * 4 = 101F かかる。
+        // 各手の 1F 目から、ツモを C 列から目的の列まで 1 列ずつ 2F おきに動かし、着いたら落下ボタンを押す。
+        let mut expected = vec![Buttons::NONE; 3 * 101];
+        expected[1] = Buttons::LEFT;
+        expected[3] = Buttons::LEFT;
+        expected[5] = Buttons::A;
+        expected[101 + 1] = Buttons::RIGHT;
+        expected[101 + 3] = Buttons::RIGHT;
+        expected[101 + 5] = Buttons::A;
+        expected[202 + 1] = Buttons::A;
+        assert_eq!(movie.frames(), expected);
+    }
+
+    #[test]
+    fn test_movie_from_solution_illegal() {
+        let pile = pile_without_yaku();
+
+        let solution: Solution = "[A, A, A, A, A, A]".parse().unwrap();
+
+        assert!(InputMovie::from_solution(
+            &InputModel::default(),
+            LEVEL_1,
+            0,
+            pile,
+            &solution,
+            &LinearFrameModel::DEFAULT
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! (フレーム数, 所持金) についてのパレートフロントの処理。
+
+use std::cmp::Reverse;
+
+use crate::Money;
+
+/// `items` を (フレーム数, 所持金) についてパレート最適なもののみに絞り、フレーム数昇順に並べる。
+///
+/// `f_key` は各要素の (フレーム数, 所持金) を返す。フレーム数と所持金が共に等しい要素たちは先頭の 1 個のみを残す。
+pub(crate) fn retain_pareto_front<T, F, K>(items: &mut Vec<T>, f_key: K)
+where
+    F: Ord,
+    K: Fn(&T) -> (F, Money),
+{
+    items.sort_by_key(|item| {
+        let (frame, money) = f_key(item);
+        (frame, Reverse(money))
+    });
+
+    // フレーム数昇順 (同じなら所持金降順) に並べたので、所持金が真に増えるものだけを残せばよい。
+    let mut money_max = None;
+    items.retain(|item| {
+        let (_, money) = f_key(item);
+        let ok = money_max.is_none_or(|money_max| money > money_max);
+        if ok {
+            money_max = Some(money);
+        }
+        ok
+    });
+}
+
+/// 長さ `len` のパレートフロントを `len_max` 個以下に間引くとき、残す要素の添字たちを昇順で返す。
+///
+/// 間引く場合は両端を含めて等間隔に選ぶ。`len_max == 1` なら先頭のみを残す。
+pub(crate) fn thin_indices(len: usize, len_max: usize) -> impl Iterator<Item = usize> {
+    assert!(len_max > 0);
+
+    let count = len.min(len_max);
+    (0..count).map(move |i| {
+        if count == len {
+            i
+        } else if count == 1 {
+            0
+        } else {
+            i * (len - 1) / (count - 1)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retain_pareto_front() {
+        let mut items: Vec<(u32, Money)> =
+            vec![(20, 20), (10, 5), (11, 5), (12, 8), (10, 3), (15, 8)];
+        retain_pareto_front(&mut items, |&item| item);
+        assert_eq!(items, [(10, 5), (12, 8), (20, 20)]);
+    }
+
+    #[test]
+    fn test_thin_indices() {
+        assert!(thin_indices(5, 3).eq([0, 2, 4]));
+        assert!(thin_indices(5, 1).eq([0]));
+        assert!(thin_indices(5, 2).eq([0, 4]));
+        assert!(thin_indices(3, 5).eq([0, 1, 2]));
+        assert!(thin_indices(0, 1).eq([]));
+    }
+}
@@ -7,7 +7,6 @@ use itertools::Itertools as _;
 use crate::board::Board;
 use crate::card::Card;
 use crate::level::*;
-use crate::square::*;
 
 /// 山札。
 ///
@@ -20,6 +19,18 @@ impl CardPile {
         Self(inner.into())
     }
 
+    /// 先頭から順に `cards` が取り出される山札を作る (テスト用)。`cards` 内に重複があってはならない。
+    #[cfg(test)]
+    pub(crate) fn from_cards(cards: &[Card]) -> Self {
+        assert_eq!(
+            cards.iter().unique().count(),
+            cards.len(),
+            "カード中に重複がある: {cards:?}"
+        );
+
+        Self::new(cards.iter().rev().copied().collect::<Vec<_>>())
+    }
+
     /// 初期山札を作る。`inner` 内に重複があってはならない。
     pub fn new_initial(inner: impl Borrow<[Card; 52]>) -> Self {
         Self::_new_initial(inner.borrow())
@@ -44,6 +55,11 @@ impl CardPile {
         self.0.is_empty()
     }
 
+    /// 山札内のカードを取り出される順に列挙する。
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Card> + ExactSizeIterator + '_ {
+        self.0.iter().copied().rev()
+    }
+
     /// 山札に 1 枚カードを戻す。
     pub fn push(&mut self, card: Card) {
         self.0.push(card);
@@ -73,7 +89,7 @@ impl CardPile {
             .with_context(|| format!("無効な山札配列メモリダンプ: '{s}'"))?;
         let s: AsciiString = s.chars().filter(|ch| !ch.is_ascii_whitespace()).collect();
         ensure!(
-            s.len() % 2 == 0,
+            s.len().is_multiple_of(2),
             "空白除去後の山札配列メモリダンプの文字数が偶数でない: '{s}'"
         );
 
@@ -100,6 +116,10 @@ impl CardPile {
     /// ゲーム内の山札配列メモリダンプ (例: "01 0A 3D ...") を初期山札としてパースする。
     ///
     /// 文字列内の ASCII 空白文字は無視される。
+    ///
+    /// NOTE: 原作のシャッフルルーチン (乱数状態の進め方と山札配列 `$0505-$0538` への書き込み) は未解析なので、
+    /// 乱数状態とレベル開始時のフレーム数から初期山札を求める手段は提供していない。
+    /// 初期山札はエミュレータ上で採取したメモリダンプから得ること。
     pub fn parse_memory_initial(s: impl AsRef<str>) -> anyhow::Result<Self> {
         Self::_parse_memory_initial(s.as_ref())
     }
@@ -117,7 +137,7 @@ impl CardPile {
     }
 
     /// 山札をゲーム内の山札配列メモリダンプとしてフォーマットする。
-    pub fn display_memory(&self) -> CardPileDisplayMemory {
+    pub fn display_memory(&self) -> CardPileDisplayMemory<'_> {
         CardPileDisplayMemory(self)
     }
 }
@@ -169,41 +189,11 @@ impl Position {
     }
 
     /// レベルと初期山札を与えて局面を初期化する。
-    pub fn with_level(level: Level, pile: CardPile) -> Self {
+    ///
+    /// 初期配置はレベルのルール ([`Level::rules`]) に従う。
+    pub fn with_level(level: Level, mut pile: CardPile) -> Self {
         assert_eq!(pile.len(), 52, "初期山札は 52 枚でなければならない");
 
-        match level {
-            LEVEL_1 | LEVEL_2 | LEVEL_3 | LEVEL_4 => Self::with_level_1_to_4(pile),
-            LEVEL_5 | LEVEL_6 | LEVEL_7 => Self::with_level_5_to_7(level, pile),
-            LEVEL_8 | LEVEL_9 | LEVEL_10 => Self::with_level_8_to_10(level, pile),
-        }
-    }
-
-    fn with_level_1_to_4(pile: CardPile) -> Self {
-        // レベル 1..=4 では初期盤面は空。
-        Self::new(Board::new(), pile)
-    }
-
-    fn with_level_5_to_7(level: Level, mut pile: CardPile) -> Self {
-        // レベル 5 では 2 枚、レベル 6..=7 では 3 枚初期配置される。
-        // これらの場合、初期配置で役ができることはない。
-
-        let mut board = Board::new();
-
-        board[SQ_A1] = Some(pile.pop().unwrap());
-        board[SQ_E1] = Some(pile.pop().unwrap());
-
-        if level >= LEVEL_6 {
-            board[SQ_C1] = Some(pile.pop().unwrap());
-        }
-
-        Self::new(board, pile)
-    }
-
-    fn with_level_8_to_10(level: Level, mut pile: CardPile) -> Self {
-        // レベル 8 では 5 枚、レベル 9..=10 では 7 枚初期配置される。
-        // これらの場合、初期配置で役ができないような調整が行われる。
-
         /// 2 つのカードが「繋がっている」かどうかを返す。
         fn is_connected(card1: Card, card2: Card) -> bool {
             let connected_suit = card1.suit() == card2.suit();
@@ -226,26 +216,19 @@ impl Position {
             pile.swap(j, k);
         }
 
-        // 原作準拠の disconnect 処理。なお、論理的には (4, 5) は disconnect する必要がない。
-        for i in 0..5 {
-            disconnect(&mut pile, i, i + 1);
-        }
-        if level >= LEVEL_9 {
-            disconnect(&mut pile, 1, 5);
-            disconnect(&mut pile, 3, 6);
+        let rules = level.rules();
+
+        // 初期配置で役ができないよう調整する (レベル 8..=10 のみ)。
+        for &(i, j) in rules.disconnect_pairs() {
+            disconnect(&mut pile, i, j);
         }
 
         // 初期配置処理。
 
         let mut board = Board::new();
 
-        for col in Col::all() {
-            let sq = Square::new(col, ROW_1);
-            board[sq] = Some(pile.pop().unwrap());
-        }
-        if level >= LEVEL_9 {
-            board[SQ_B2] = Some(pile.pop().unwrap());
-            board[SQ_D2] = Some(pile.pop().unwrap());
+        for &sq in rules.initial_squares() {
+            board.set(sq, Some(pile.pop().unwrap()));
         }
 
         Self::new(board, pile)
@@ -379,4 +362,18 @@ mod tests {
             assert_eq!(*pos.pile(), pile_expect);
         }
     }
+
+    #[test]
+    fn test_position_with_level_ply_count() {
+        for level in Level::all() {
+            let pos = Position::with_level(level, cheat_pile());
+            let rules = level.rules();
+            assert_eq!(pos.pile().len(), rules.ply_count(), "{level:?}");
+            assert_eq!(
+                pos.board().card_count(),
+                rules.initial_squares().len(),
+                "{level:?}"
+            );
+        }
+    }
 }
@@ -1,14 +1,20 @@
 use anyhow::{anyhow, bail};
 use bitvec::prelude::*;
 
+use crate::board::Board;
+use crate::card::Card;
+use crate::frame_model::FrameModel;
+use crate::level::Level;
+use crate::position::{CardPile, Position};
 use crate::square::{Col, COL_A, COL_B, COL_C, COL_D, COL_E};
-use crate::PLY_COUNT_MAX;
+use crate::yaku::{process_yaku_chain_detail, YakuChain, YakuEvent};
+use crate::{Frame, Money, PLY_COUNT_MAX};
 
-/// ゲームの解手順。レベル 9, 10 専用。
+/// ゲームの解手順 (1 レベル分)。
 ///
 /// 1 手あたり 3bit で記録する。
-/// レベル 9 または 10 は 45 手かかるので、解は `3 * 45 = 135` bit となる。
-#[derive(Clone, Default, Eq, PartialEq)]
+/// 1 レベルは最大 52 手かかるので、解は最大 `3 * 52 = 156` bit となる。
+#[derive(Clone, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Solution(BitArr!(for 3 * PLY_COUNT_MAX, in u32));
 
 impl Solution {
@@ -50,12 +56,146 @@ impl Solution {
     }
 
     /// 手を最初から順に列挙する。
-    pub fn iter(&self) -> impl Iterator<Item = Col> + std::iter::FusedIterator + '_ {
+    pub fn iter(&self) -> impl std::iter::FusedIterator<Item = Col> + '_ {
         (0..PLY_COUNT_MAX)
             .map(|ply| self.get_move(ply))
             .fuse()
             .flatten()
     }
+
+    /// 先頭 `len` 手のみからなる手順を返す。
+    pub fn prefix(&self, len: usize) -> Self {
+        self.iter()
+            .take(len)
+            .enumerate()
+            .fold(Self::new(), |sol, (ply, mv)| sol.add_move(ply, mv))
+    }
+
+    /// レベル `level` を所持金 `money`、初期山札 `pile` で開始して手順を再生し、1 手ごとの記録を返す。
+    /// フレームコストは `model` に従う。
+    ///
+    /// 無効な着手を含む場合、あるいは途中で山札が尽きた場合はエラーを返す。
+    pub fn replay<M: FrameModel + ?Sized>(
+        &self,
+        level: Level,
+        money: Money,
+        pile: CardPile,
+        model: &M,
+    ) -> anyhow::Result<Vec<PlyRecord>> {
+        let (mut board, mut pile) = Position::with_level(level, pile).destructure();
+        let mut money = money;
+        let mut frame_elapsed = 0;
+
+        let mut records = Vec::<PlyRecord>::with_capacity(self.len());
+
+        for (ply, col) in self.iter().enumerate() {
+            let card = pile
+                .pop()
+                .ok_or_else(|| anyhow!("ply {ply}: 山札が尽きている"))?;
+            let Some((mut board_after, row)) = board.put(col, card) else {
+                bail!("ply {ply}: 列 {col} にカード {card} を置けない");
+            };
+            let frame_put = model.put(row);
+            let chain = process_yaku_chain_detail(&mut board_after, model);
+
+            money += chain.prize();
+            frame_elapsed += frame_put + chain.frame();
+
+            let board_before = std::mem::replace(&mut board, board_after.clone());
+            records.push(PlyRecord {
+                card,
+                col,
+                board_before,
+                board_after,
+                frame_put,
+                frame_elapsed,
+                money,
+                chain,
+            });
+        }
+
+        Ok(records)
+    }
+}
+
+/// 手順の再生における 1 手分の記録。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlyRecord {
+    card: Card,
+    col: Col,
+    board_before: Board,
+    board_after: Board,
+    frame_put: Frame,
+    frame_elapsed: Frame,
+    money: Money,
+    chain: YakuChain,
+}
+
+impl PlyRecord {
+    /// ツモを返す。
+    pub fn card(&self) -> Card {
+        self.card
+    }
+
+    /// ツモを置いた列を返す。
+    pub fn col(&self) -> Col {
+        self.col
+    }
+
+    /// 着手前の盤面を返す。
+    pub fn board_before(&self) -> &Board {
+        &self.board_before
+    }
+
+    /// 全ての連鎖処理が完了した後の盤面を返す。
+    pub fn board_after(&self) -> &Board {
+        &self.board_after
+    }
+
+    /// ツモを置くのにかかったフレーム数を返す。
+    pub fn frame_put(&self) -> Frame {
+        self.frame_put
+    }
+
+    /// 役消去後の落下にかかったフレーム数を返す。
+    pub fn frame_fall(&self) -> Frame {
+        self.chain.frame_fall()
+    }
+
+    /// 役成立演出およびカード消去にかかったフレーム数を返す。
+    pub fn frame_yaku(&self) -> Frame {
+        self.chain.frame_yaku()
+    }
+
+    /// この手にかかったフレーム数を返す。
+    pub fn frame(&self) -> Frame {
+        self.frame_put + self.chain.frame()
+    }
+
+    /// レベル開始からこの手の完了までの累計フレーム数を返す。
+    pub fn frame_elapsed(&self) -> Frame {
+        self.frame_elapsed
+    }
+
+    /// この手で得られた賞金を返す。
+    pub fn prize(&self) -> Money {
+        self.chain.prize()
+    }
+
+    /// この手の完了後の所持金を返す。
+    pub fn money(&self) -> Money {
+        self.money
+    }
+
+    /// 連鎖の段数を返す。役が一切成立しなければ 0。
+    pub fn chain_depth(&self) -> usize {
+        self.chain.depth()
+    }
+
+    /// 連鎖の各ステップの詳細を返す。
+    pub fn yaku_events(&self) -> &[YakuEvent] {
+        self.chain.events()
+    }
 }
 
 impl std::str::FromStr for Solution {
@@ -124,7 +264,12 @@ impl std::fmt::Display for MoveDisplay {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools as _;
+
+    use crate::frame_model::LinearFrameModel;
+    use crate::level::*;
     use crate::square::*;
+    use crate::state::State;
 
     use super::*;
 
@@ -181,4 +326,40 @@ mod tests {
         let sol: Solution = case.parse().unwrap();
         assert_eq!(sol.to_string(), case);
     }
+
+    #[test]
+    fn test_replay() {
+        let model = LinearFrameModel::default();
+        let pile = || CardPile::new_initial(Card::all());
+
+        // 各手で置ける最も左の列を選ぶ。
+        let states = State::play_leftmost(LEVEL_9, 10, pile(), 20, &model);
+        let state = states.last().unwrap();
+
+        let sol = state.solution();
+        let records = sol.replay(LEVEL_9, 10, pile(), &model).unwrap();
+        assert_eq!(records.len(), 20);
+        for (ply, (record, state)) in std::iter::zip(&records, &states).enumerate() {
+            assert_eq!(Some(record.col()), sol.get_move(ply));
+            assert_eq!(record.board_after(), state.board());
+            assert_eq!(record.money(), state.money());
+            assert_eq!(record.frame_elapsed(), state.frame());
+            assert_eq!(record.prize() == 0, record.chain_depth() == 0);
+        }
+        for (prev, record) in records.iter().tuple_windows() {
+            assert_eq!(record.board_before(), prev.board_after());
+            assert_eq!(
+                record.frame_elapsed(),
+                prev.frame_elapsed() + record.frame()
+            );
+        }
+
+        assert_eq!(sol.prefix(5).len(), 5);
+        assert!(sol.prefix(5).iter().eq(sol.iter().take(5)));
+
+        // 同じ列に置き続けるといずれ置けなくなる。
+        let sol: Solution = "[A, A, A, A, A, A, A, A]".parse().unwrap();
+        let err = sol.replay(LEVEL_9, 0, pile(), &model).unwrap_err();
+        assert!(err.to_string().starts_with("ply "), "{err}");
+    }
 }
@@ -4,6 +4,7 @@ use arrayvec::ArrayVec;
 
 use crate::board::Board;
 use crate::card::Card;
+use crate::frame_model::FrameModel;
 use crate::level::Level;
 use crate::position::{CardPile, Position};
 use crate::solution::Solution;
@@ -12,7 +13,7 @@ use crate::yaku::process_yaku_chain;
 use crate::{Frame, Money};
 
 /// 探索中の状態。
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct State {
     frame: Frame,
     money: Money,
@@ -58,14 +59,21 @@ impl State {
     }
 
     /// 現在の状態を `ply` 手目 (0-based)、ツモを `card` としたときの近傍状態を列挙する。
-    pub fn neighbors(&self, ply: usize, card: Card) -> ArrayVec<Self, 5> {
+    /// フレームコストは `model` に従う。
+    pub fn neighbors<M: FrameModel + ?Sized>(
+        &self,
+        ply: usize,
+        card: Card,
+        model: &M,
+    ) -> ArrayVec<Self, 5> {
         let mut res = ArrayVec::<Self, 5>::new();
 
         for col in Col::all() {
-            let Some((mut board, frame_put)) = self.board.put(col, card) else {
+            let Some((mut board, row)) = self.board.put(col, card) else {
                 continue;
             };
-            let (frame_yaku, prize) = process_yaku_chain(&mut board);
+            let frame_put = model.put(row);
+            let (frame_yaku, prize) = process_yaku_chain(&mut board, model);
             let state = Self::new(
                 self.frame + frame_put + frame_yaku,
                 self.money + prize,
@@ -79,9 +87,17 @@ impl State {
     }
 
     /// 指定した着手を行った後の状態を返す。着手は有効であることを仮定している。
-    pub fn do_move(&self, ply: usize, card: Card, col: Col) -> Self {
-        let (mut board, frame_put) = self.board.put(col, card).unwrap();
-        let (frame_yaku, prize) = process_yaku_chain(&mut board);
+    /// フレームコストは `model` に従う。
+    pub fn do_move<M: FrameModel + ?Sized>(
+        &self,
+        ply: usize,
+        card: Card,
+        col: Col,
+        model: &M,
+    ) -> Self {
+        let (mut board, row) = self.board.put(col, card).unwrap();
+        let frame_put = model.put(row);
+        let (frame_yaku, prize) = process_yaku_chain(&mut board, model);
         Self::new(
             self.frame + frame_put + frame_yaku,
             self.money + prize,
@@ -90,11 +106,43 @@ impl State {
         )
     }
 
+    /// レベル `level` を所持金 `money`、初期山札 `pile` で始め、各手で置ける最も左の列を選んで `ply_count` 手進めたときの、
+    /// 各手の後の状態たちを返す (テスト用)。フレームコストは `model` に従う。
+    #[cfg(test)]
+    pub(crate) fn play_leftmost<M: FrameModel + ?Sized>(
+        level: Level,
+        money: Money,
+        pile: CardPile,
+        ply_count: usize,
+        model: &M,
+    ) -> Vec<Self> {
+        let (mut state, mut pile) = Self::new_initial(level, money, pile);
+        let mut states = Vec::with_capacity(ply_count);
+        for ply in 0..ply_count {
+            let card = pile.pop().unwrap();
+            let col = Col::all()
+                .into_iter()
+                .find(|&col| state.board().put(col, card).is_some())
+                .unwrap();
+            state = state.do_move(ply, card, col, model);
+            states.push(state.clone());
+        }
+
+        states
+    }
+
     /// 手順前後を無視して等しいかどうかを返す。
     pub fn eq_ignore_solution(&self, other: &Self) -> bool {
         (self.frame, self.money, &self.board) == (other.frame, other.money, &other.board)
     }
 
+    /// 手順前後を無視して、`self` が `other` 以上に良い状態かどうかを返す。
+    ///
+    /// 盤面が等しく、フレーム数が `other` 以下かつ所持金が `other` 以上のとき真。
+    pub fn dominates(&self, other: &Self) -> bool {
+        self.board == other.board && self.frame <= other.frame && self.money >= other.money
+    }
+
     /// 手順前後を無視して大小比較する。
     pub fn cmp_ignore_solution(&self, other: &Self) -> Ordering {
         (self.frame, self.money, &self.board).cmp(&(other.frame, other.money, &other.board))
@@ -117,6 +165,7 @@ mod tests {
     use indoc::indoc;
 
     use crate::card::*;
+    use crate::frame_model::LinearFrameModel;
 
     use super::*;
 
@@ -135,7 +184,7 @@ mod tests {
         "});
 
         let state = State::new(0, 0, board, Solution::new());
-        let neighbors = state.neighbors(0, CARD_H2);
+        let neighbors = state.neighbors(0, CARD_H2, &LinearFrameModel::DEFAULT);
 
         assert_eq!(neighbors[0].frame(), 101 + 72 + 24 + 8);
         assert_eq!(neighbors[1].frame(), 85);
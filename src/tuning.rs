@@ -0,0 +1,233 @@
+//! 中盤探索の評価関数のパラメータ調整。
+
+use rand::prelude::*;
+
+use crate::endgame::{solve_endgame_all, CollectSink};
+use crate::evaluator::{Evaluator, ParamEvaluator, PlySchedule};
+use crate::frame_model::FrameModel;
+use crate::level::{ClearCondition, Level};
+use crate::midgame::solve_midgame;
+use crate::position::CardPile;
+use crate::state::State;
+use crate::{Frame, Money, TotalFrame};
+
+/// 評価関数のパラメータを山札コーパス上で評価した結果。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CorpusScore {
+    frames: Vec<Option<Frame>>,
+}
+
+impl CorpusScore {
+    /// 各山札について見つかった最速解のフレーム数から作る。解が見つからなかった山札は `None` とする。
+    pub fn new(frames: Vec<Option<Frame>>) -> Self {
+        Self { frames }
+    }
+
+    /// 各山札について見つかった最速解のフレーム数を返す。解が見つからなかった山札は `None`。
+    pub fn frames(&self) -> &[Option<Frame>] {
+        &self.frames
+    }
+
+    /// 解が見つからなかった山札の個数を返す。
+    pub fn failed_count(&self) -> usize {
+        self.frames.iter().filter(|frame| frame.is_none()).count()
+    }
+
+    /// 解が見つかった山札についての最速解のフレーム数の平均を返す。1 つも見つからなければ `None`。
+    pub fn frame_mean(&self) -> Option<f64> {
+        let solved: Vec<_> = self.frames.iter().flatten().copied().collect();
+
+        (!solved.is_empty()).then(|| {
+            solved.iter().map(|&frame| f64::from(frame)).sum::<f64>() / solved.len() as f64
+        })
+    }
+
+    /// `self` が `other` より真に良い結果かどうかを返す。`self` と `other` は同じコーパス上の結果でなければならない。
+    ///
+    /// 解が見つからなかった山札が少ないほど良く、それが等しければ両方で解が見つかった山札についての
+    /// フレーム数の合計が小さいほど良い (解けた山札の組が異なる平均どうしを比べると、難しい山札を落とした方が有利になるため)。
+    pub fn is_better_than(&self, other: &Self) -> bool {
+        assert_eq!(self.frames.len(), other.frames.len());
+
+        let failed = self.failed_count();
+        let failed_other = other.failed_count();
+        if failed != failed_other {
+            return failed < failed_other;
+        }
+
+        let (sum, sum_other) = self
+            .frames
+            .iter()
+            .zip(&other.frames)
+            .filter_map(|(&frame, &frame_other)| Some((frame?, frame_other?)))
+            .fold((0, 0), |(sum, sum_other), (frame, frame_other)| {
+                (
+                    sum + TotalFrame::from(frame),
+                    sum_other + TotalFrame::from(frame_other),
+                )
+            });
+        sum < sum_other
+    }
+}
+
+impl std::fmt::Display for CorpusScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.frame_mean() {
+            Some(mean) => write!(f, "mean={mean:.2}")?,
+            None => f.write_str("mean=-")?,
+        }
+        write!(f, " failed={}/{}", self.failed_count(), self.frames.len())
+    }
+}
+
+/// 山札コーパス `piles` の各山札について中盤探索と終盤完全読みを行い、評価関数 `evaluator` の性能を測る。
+///
+/// 各山札について、`midgame_beam_width` の `solve_midgame` で残り `endgame_len` 手まで探索し、
+/// 上位 `endgame_state_count` 件の状態から `solve_endgame_all` でクリア条件 `cond` を満たす解を完全読みする。
+/// 中盤探索では同一盤面についてフレーム数最小の状態のみを残す。
+/// 調整を高速に回すため、ビーム幅や完全読み手数は本番の探索より小さくすることを想定している。
+/// フレームコストは `model` に従う。
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_corpus<M: FrameModel + ?Sized>(
+    level: Level,
+    cond: ClearCondition,
+    money: Money,
+    piles: &[CardPile],
+    midgame_beam_width: usize,
+    endgame_state_count: usize,
+    endgame_len: usize,
+    rng_seed: u64,
+    model: &M,
+    evaluator: &dyn Evaluator,
+) -> CorpusScore {
+    let ply_count = level.rules().ply_count();
+    assert!(endgame_len <= ply_count);
+
+    let frames = piles
+        .iter()
+        .map(|pile| {
+            let (mut states, pile) = solve_midgame(
+                level,
+                money,
+                pile.clone(),
+                ply_count - endgame_len,
+                midgame_beam_width,
+                1,
+                rng_seed,
+                model,
+                evaluator,
+            );
+            states.truncate(endgame_state_count);
+
+            let sink = CollectSink::new();
+            solve_endgame_all(level, cond, &pile, states, Frame::MAX, model, &sink);
+            // 通知される解のフレーム数は狭義単調減少なので、最後のものが最速解。
+            sink.into_answers().last().map(State::frame)
+        })
+        .collect();
+
+    CorpusScore::new(frames)
+}
+
+/// 評価関数のパラメータ `evaluator` をランダムに摂動したものを返す (ランダム探索の近傍)。
+///
+/// `frame_weight` は評価値のスケールを決めるだけなので固定し、それ以外の各値を
+/// 相対幅 `step` 程度の一様乱数で動かす。値は非負に保たれ、スケジュールの区切りは変えない。
+pub fn perturb_evaluator(
+    rng: &mut impl Rng,
+    evaluator: &ParamEvaluator,
+    step: f64,
+) -> ParamEvaluator {
+    // 値が 0 付近でも動けるよう、摂動幅にはパラメータごとの最小スケールを設ける。
+    const MONEY_WEIGHT_SCALE_MIN: f64 = 1.0;
+    const CARD_COUNT_WEIGHT_SCALE_MIN: f64 = 10.0;
+    const NOISE_SCALE_MIN: f64 = 10.0;
+
+    fn perturb(rng: &mut impl Rng, value: f64, step: f64, scale_min: f64) -> f64 {
+        let delta = step * value.abs().max(scale_min) * rng.gen_range(-1.0..=1.0);
+        (value + delta).max(0.0)
+    }
+
+    fn perturb_schedule(
+        rng: &mut impl Rng,
+        schedule: &PlySchedule,
+        step: f64,
+        scale_min: f64,
+    ) -> PlySchedule {
+        PlySchedule::new(
+            schedule
+                .steps()
+                .iter()
+                .map(|&(rest_min, value)| (rest_min, perturb(rng, value, step, scale_min)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    ParamEvaluator {
+        frame_weight: evaluator.frame_weight,
+        money_weight: perturb(rng, evaluator.money_weight, step, MONEY_WEIGHT_SCALE_MIN),
+        card_count_weight: perturb_schedule(
+            rng,
+            &evaluator.card_count_weight,
+            step,
+            CARD_COUNT_WEIGHT_SCALE_MIN,
+        ),
+        noise: perturb_schedule(rng, &evaluator.noise, step, NOISE_SCALE_MIN),
+        prune_unclearable: evaluator.prune_unclearable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_score() {
+        let score = CorpusScore::new(vec![Some(100), None, Some(200)]);
+        assert_eq!(score.failed_count(), 1);
+        assert_eq!(score.frame_mean(), Some(150.0));
+        assert_eq!(score.to_string(), "mean=150.00 failed=1/3");
+
+        let fast = CorpusScore::new(vec![Some(100), None, Some(100)]);
+        let solved = CorpusScore::new(vec![Some(300), Some(300), Some(300)]);
+        let failed = CorpusScore::new(vec![None, None, None]);
+        assert!(fast.is_better_than(&score));
+        assert!(!score.is_better_than(&fast));
+        assert!(!score.is_better_than(&score));
+        assert!(solved.is_better_than(&fast));
+        assert!(score.is_better_than(&failed));
+        assert_eq!(failed.frame_mean(), None);
+
+        // 平均は `other` の方が小さいが、両方で解けた山札 (0 番目) では `hard` の方が速い。
+        let hard = CorpusScore::new(vec![Some(100), Some(300), None]);
+        let other = CorpusScore::new(vec![Some(110), None, Some(100)]);
+        assert!(hard.is_better_than(&other));
+        assert!(!other.is_better_than(&hard));
+    }
+
+    #[test]
+    fn test_perturb_evaluator() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        let evaluator = ParamEvaluator::default();
+
+        for _ in 0..100 {
+            let perturbed = perturb_evaluator(&mut rng, &evaluator, 0.5);
+            assert_eq!(perturbed.frame_weight, evaluator.frame_weight);
+            assert!(perturbed.money_weight >= 0.0);
+
+            for (schedule, schedule_orig) in [
+                (&perturbed.card_count_weight, &evaluator.card_count_weight),
+                (&perturbed.noise, &evaluator.noise),
+            ] {
+                assert_eq!(schedule.steps().len(), schedule_orig.steps().len());
+                for (&(rest_min, value), &(rest_min_orig, value_orig)) in
+                    schedule.steps().iter().zip(schedule_orig.steps())
+                {
+                    assert_eq!(rest_min, rest_min_orig);
+                    assert!(value >= 0.0);
+                    assert!((value - value_orig).abs() <= 0.5 * value_orig.max(10.0));
+                }
+            }
+        }
+    }
+}
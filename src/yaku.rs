@@ -1,7 +1,8 @@
 //! 役検出および賞金計算。
 
 use crate::board::Board;
-use crate::card::{Card, CardRank, RANK_A, RANK_J, RANK_K, RANK_Q, RANK_T};
+use crate::card::{Card, CardRank, CardSuit, RANK_A, RANK_J, RANK_K, RANK_Q, RANK_T};
+use crate::frame_model::FrameModel;
 use crate::square::{Col, Row, Square};
 use crate::{Frame, Money};
 
@@ -11,50 +12,212 @@ use crate::{Frame, Money};
 /// 与えられた盤面は全てのカードが落下済みと仮定している。
 ///
 /// この関数が 0 を返すことと役が一切成立しないことは同値。
-pub fn process_yaku_chain(board: &mut Board) -> (Frame, Money) {
+pub fn process_yaku_chain<M: FrameModel + ?Sized>(board: &mut Board, model: &M) -> (Frame, Money) {
     let mut frame = 0;
     let mut prize = 0;
 
-    loop {
-        let (frame_cur, prize_cur) = process_yaku_step(board);
-        if prize_cur == 0 {
+    for chain in 0.. {
+        let step = process_yaku_step(board, model, chain);
+        if step.prize == 0 {
             break;
         }
-        frame += frame_cur;
-        prize += prize_cur;
+        frame += step.frame_yaku + step.frame_fall;
+        prize += step.prize;
     }
 
     (frame, prize)
 }
 
-/// 与えられた盤面に対して役検出/処理を行い、(フレームコスト, 得られた賞金) を返す (連鎖処理なし)。
+/// `process_yaku_chain` と同様の処理を行い、連鎖の各ステップで何が起こったかを返す。
+///
+/// 役の列挙などを行うため `process_yaku_chain` より遅い。探索中には使わないこと。
+pub fn process_yaku_chain_detail<M: FrameModel + ?Sized>(
+    board: &mut Board,
+    model: &M,
+) -> YakuChain {
+    let mut events = Vec::<YakuEvent>::new();
+
+    for chain in 0.. {
+        let Some(event) = process_yaku_event(board, model, chain) else {
+            break;
+        };
+        events.push(event);
+    }
+
+    YakuChain { events }
+}
+
+/// 連鎖処理の詳細。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct YakuChain {
+    events: Vec<YakuEvent>,
+}
+
+impl YakuChain {
+    /// 連鎖の各ステップの詳細を返す。役が一切成立しなければ空。
+    pub fn events(&self) -> &[YakuEvent] {
+        &self.events
+    }
+
+    /// 役成立演出およびカード消去にかかったフレーム数を返す。
+    pub fn frame_yaku(&self) -> Frame {
+        self.events.iter().map(YakuEvent::frame_yaku).sum()
+    }
+
+    /// 消去後の落下にかかったフレーム数を返す。
+    pub fn frame_fall(&self) -> Frame {
+        self.events.iter().map(YakuEvent::frame_fall).sum()
+    }
+
+    /// フレームコストの合計を返す。
+    pub fn frame(&self) -> Frame {
+        self.frame_yaku() + self.frame_fall()
+    }
+
+    /// 得られた賞金を返す。
+    pub fn prize(&self) -> Money {
+        self.events.iter().map(YakuEvent::prize).sum()
+    }
+
+    /// 連鎖の段数 (役が成立した回数) を返す。役が一切成立しなければ 0。
+    pub fn depth(&self) -> usize {
+        self.events.len()
+    }
+}
+
+/// 連鎖の 1 ステップの詳細。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YakuEvent {
+    hands: Vec<Hand>,
+    prize_base: Money,
+    multiplier: Money,
+    cards_removed: Vec<(Square, Card)>,
+    frame_yaku: Frame,
+    frame_fall: Frame,
+}
+
+impl YakuEvent {
+    /// 成立した役たちを返す。
+    pub fn hands(&self) -> &[Hand] {
+        &self.hands
+    }
+
+    /// 倍率を掛ける前の賞金 (各役の賞金の和) を返す。
+    pub fn prize_base(&self) -> Money {
+        self.prize_base
+    }
+
+    /// 役に絡んだカードの枚数による賞金の倍率を返す。
+    pub fn multiplier(&self) -> Money {
+        self.multiplier
+    }
+
+    /// 得られた賞金を返す。
+    pub fn prize(&self) -> Money {
+        self.prize_base * self.multiplier
+    }
+
+    /// 消去された (マス, カード) たちを返す。
+    pub fn cards_removed(&self) -> &[(Square, Card)] {
+        &self.cards_removed
+    }
+
+    /// 役成立演出およびカード消去にかかったフレーム数を返す。
+    pub fn frame_yaku(&self) -> Frame {
+        self.frame_yaku
+    }
+
+    /// 消去後の落下にかかったフレーム数を返す。
+    pub fn frame_fall(&self) -> Frame {
+        self.frame_fall
+    }
+}
+
+/// 連鎖の 1 ステップの処理結果。
+#[derive(Debug)]
+struct YakuStep {
+    frame_yaku: Frame,
+    frame_fall: Frame,
+    prize: Money,
+}
+
+/// 与えられた盤面に対して役検出/処理を行い、処理結果を返す (連鎖処理なし)。
 /// 盤面は 1 ステップ後の消去/落下処理が完了した後の状態となる。
+/// `chain` は連鎖の段数 (0-based)。
 ///
 /// 与えられた盤面は全てのカードが落下済みと仮定している。
 ///
-/// この関数が 0 を返すことと役が一切成立しないことは同値。
-fn process_yaku_step(board: &mut Board) -> (Frame, Money) {
+/// 賞金が 0 であることと役が一切成立しないことは同値。
+fn process_yaku_step<M: FrameModel + ?Sized>(
+    board: &mut Board,
+    model: &M,
+    chain: usize,
+) -> YakuStep {
     // 役検出と賞金加算処理は分離されている。挙動が非自明なので愚直にシミュレートする。
 
     let yaku_board = detect_yaku(board);
 
     let prize = calc_prize(board, &yaku_board);
+    if prize == 0 {
+        return YakuStep {
+            frame_yaku: 0,
+            frame_fall: 0,
+            prize: 0,
+        };
+    }
 
-    // 役成立演出に 72F かかるとする (概算)。
-    let mut frame = 72;
-
-    // カード 1 枚の消去に 8F かかるとする (概算)。
+    let mut card_count = 0;
     for sq in yaku_board.squares_nonzero() {
-        board[sq] = None;
-        frame += 8;
+        board.set(sq, None);
+        card_count += 1;
     }
-    frame += board.fall();
+    let dist = board.fall();
 
-    (frame, prize)
+    YakuStep {
+        frame_yaku: model.yaku(chain, card_count),
+        frame_fall: model.fall(dist),
+        prize,
+    }
+}
+
+/// `process_yaku_step` と同様の処理を行い、その詳細を返す。役が一切成立しなければ `None` を返す。
+fn process_yaku_event<M: FrameModel + ?Sized>(
+    board: &mut Board,
+    model: &M,
+    chain: usize,
+) -> Option<YakuEvent> {
+    let yaku_board = detect_yaku(board);
+
+    let mut hands = Vec::<Hand>::new();
+    for_each_hand(board, &yaku_board, |hand| hands.push(hand));
+
+    let prize_base: Money = hands.iter().copied().map(Hand::prize).sum();
+    if prize_base == 0 {
+        return None;
+    }
+    let multiplier = prize_multiplier(yaku_board.count_nonzero());
+
+    let mut cards_removed = Vec::<(Square, Card)>::new();
+    for sq in yaku_board.squares_nonzero() {
+        cards_removed.push((sq, board[sq].unwrap()));
+        board.set(sq, None);
+    }
+    let dist = board.fall();
+
+    Some(YakuEvent {
+        hands,
+        prize_base,
+        multiplier,
+        frame_yaku: model.yaku(chain, cards_removed.len()),
+        frame_fall: model.fall(dist),
+        cards_removed,
+    })
 }
 
-/// 与えられた盤面に対して役検出を行い、`YakuBoard` を返す。
-fn detect_yaku(board: &Board) -> YakuBoard {
+/// 与えられた盤面に対して役検出を行い、`YakuBoard` を返す。盤面は変更しない。
+///
+/// 与えられた盤面は全てのカードが落下済みと仮定している。
+pub fn detect_yaku(board: &Board) -> YakuBoard {
     let mut yaku_board = YakuBoard::new();
 
     detect_straight(board, &mut yaku_board);
@@ -223,7 +386,7 @@ fn flush_len(ary: &[Option<Card>]) -> usize {
     };
 
     ary.iter()
-        .position(|card| card.map_or(true, |card| card.suit() != first.suit()))
+        .position(|card| card.is_none_or(|card| card.suit() != first.suit()))
         .unwrap_or(ary.len())
 }
 
@@ -282,7 +445,7 @@ fn n_of_kind_len(ary: &[Option<Card>]) -> usize {
     };
 
     ary.iter()
-        .position(|card| card.map_or(true, |card| card.rank() != first.rank()))
+        .position(|card| card.is_none_or(|card| card.rank() != first.rank()))
         .unwrap_or(ary.len())
 }
 
@@ -290,6 +453,9 @@ fn n_of_kind_len(ary: &[Option<Card>]) -> usize {
 //
 // ロイヤルフラッシュは単独で成立したとき 5 枚ストレートフラッシュ、5 枚ストレート、5 枚フラッシュと複合する。
 // ストレートフラッシュは単独で成立したときストレートおよびフラッシュと複合する。
+/// ロイヤルフラッシュを構成するランクたち。
+const ROYAL_RANKS: [CardRank; 5] = [RANK_T, RANK_J, RANK_Q, RANK_K, RANK_A];
+
 const PRIZE_ROYAL_FLUSH: Money = 200;
 const PRIZE_STRAIGHT_FLUSH_5: Money = 120;
 const PRIZE_STRAIGHT_FLUSH_4: Money = 40;
@@ -340,8 +506,23 @@ fn prize_n_of_kind(len: usize) -> Money {
     }
 }
 
-/// 役検出結果から賞金総額を求める。
-fn calc_prize(board: &Board, yaku_board: &YakuBoard) -> Money {
+/// 与えられた盤面に対して役検出を行い、成立した全ての役を返す。盤面は変更しない。
+/// 連鎖は考慮しない (現在の盤面で即座に成立するもののみ)。
+///
+/// 与えられた盤面は全てのカードが落下済みと仮定している。
+pub fn detect_hands(board: &Board) -> Vec<Hand> {
+    let yaku_board = detect_yaku(board);
+
+    let mut hands = Vec::<Hand>::new();
+    for_each_hand(board, &yaku_board, |hand| hands.push(hand));
+
+    hands
+}
+
+/// 盤面 `board` とその役検出結果 `yaku_board` から賞金総額 (倍率込み) を求める。
+///
+/// 探索中に呼ばれるため、役の列挙は行わない。成立した役を知りたい場合は `detect_hands` を用いること。
+pub fn calc_prize(board: &Board, yaku_board: &YakuBoard) -> Money {
     let mut prize = 0;
 
     prize += calc_prize_straight_flush(board, yaku_board);
@@ -354,18 +535,7 @@ fn calc_prize(board: &Board, yaku_board: &YakuBoard) -> Money {
     }
 
     // 役に絡んだカードの枚数により倍率が掛かる。
-    prize *= match yaku_board.count_nonzero() {
-        0..=5 => 1,
-        6 => 2,
-        7 => 3,
-        8 => 5,
-        9 => 6,
-        10 => 7,
-        11 => 8,
-        _ => 10,
-    };
-
-    prize
+    prize * prize_multiplier(yaku_board.count_nonzero())
 }
 
 /// 検出された全てのストレートフラッシュおよびロイヤルフラッシュの賞金総額を返す。
@@ -422,14 +592,6 @@ fn calc_prize_straight_flush_col(yaku_board: &YakuBoard, col: Col) -> Money {
     0
 }
 
-/// ランク配列がロイヤルフラッシュの条件を満たすかどうかを返す。
-fn ranks_is_royal(ranks: &[CardRank; 5]) -> bool {
-    matches!(
-        ranks,
-        [RANK_T, RANK_J, RANK_Q, RANK_K, RANK_A] | [RANK_A, RANK_K, RANK_Q, RANK_J, RANK_T]
-    )
-}
-
 /// 検出された全てのストレートの賞金総額を返す。
 fn calc_prize_straight(yaku_board: &YakuBoard) -> Money {
     let mut prize = 0;
@@ -565,6 +727,272 @@ fn calc_prize_n_of_kind_col(yaku_board: &YakuBoard, col: Col) -> Money {
     0
 }
 
+/// 役に絡んだカードの枚数に対する賞金の倍率を返す。
+fn prize_multiplier(card_count: usize) -> Money {
+    match card_count {
+        0..=5 => 1,
+        6 => 2,
+        7 => 3,
+        8 => 5,
+        9 => 6,
+        10 => 7,
+        11 => 8,
+        _ => 10,
+    }
+}
+
+/// カード集合 `cards` のカードのみを消去して今後得られる賞金総額の上界を返す。
+///
+/// 各カードについて、それを含みうる役の賞金を構成枚数で按分したものの上界を種類ごとに求め、
+/// 行と列の 2 ライン分を足し合わせる。
+/// 1 ステップの倍率は消去枚数について単調なので、全てのカードが 1 ステップで消去される場合の倍率を掛ける。
+pub(crate) fn calc_prize_upper_bound(cards: &[Card]) -> Money {
+    let set = CardSet::from_cards(cards);
+
+    let share_sum: u32 = cards.iter().map(|&card| 2 * set.hand_share(card)).sum();
+
+    let multiplier = u32::from(prize_multiplier(cards.len().min(Square::NUM)));
+
+    (multiplier * share_sum)
+        .div_ceil(SHARE_UNIT)
+        .min(u32::from(Money::MAX)) as Money
+}
+
+/// 按分した賞金の単位 (役の枚数 3, 4, 5 の最小公倍数分の 1)。
+const SHARE_UNIT: u32 = 60;
+
+/// 役の成立可能性を調べるためのカード集合。スートごとにランクのビットマスク (bit i がランク i+1) で表す。
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct CardSet([u16; CardSuit::NUM]);
+
+impl CardSet {
+    pub(crate) fn from_cards<'a>(cards: impl IntoIterator<Item = &'a Card>) -> Self {
+        let mut set = Self::default();
+        for &card in cards {
+            set.insert(card);
+        }
+        set
+    }
+
+    pub(crate) fn insert(&mut self, card: Card) {
+        self.0[card.suit().to_index()] |= 1 << card.rank().to_index();
+    }
+
+    /// カード `card` が、集合内のカードのみからなる何らかの役に含まれうるかどうかを返す。
+    /// `card` 自身は集合に含まれていると仮定している。
+    ///
+    /// ストレートフラッシュが成立しうるならばストレートも成立しうるので、それ以外の種類のみを調べればよい。
+    pub(crate) fn can_form_hand(&self, card: Card) -> bool {
+        self.suit_count(card.suit()) >= 3
+            || self.rank_count(card.rank()) >= 3
+            || Self::run_len(self.rank_mask(), card.rank()) >= 3
+    }
+
+    /// カード `card` を含みうる役の賞金を構成枚数で按分したものの、1 ラインあたりの上界を `SHARE_UNIT` 単位で返す。
+    /// `card` 自身は集合に含まれていると仮定している。役に含まれえないならば 0 を返す。
+    fn hand_share(&self, card: Card) -> u32 {
+        fn share_max(len_max: usize, prize: fn(usize) -> Money) -> u32 {
+            (3..=len_max.min(5))
+                .map(|len| u32::from(prize(len)) * SHARE_UNIT / len as u32)
+                .max()
+                .unwrap_or(0)
+        }
+
+        let suit_mask = self.0[card.suit().to_index()];
+        let share_royal = if ROYAL_RANKS.contains(&card.rank())
+            && ROYAL_RANKS
+                .iter()
+                .all(|rank| suit_mask & (1 << rank.to_index()) != 0)
+        {
+            u32::from(PRIZE_ROYAL_FLUSH) * SHARE_UNIT / 5
+        } else {
+            0
+        };
+
+        share_max(Self::run_len(suit_mask, card.rank()), prize_straight_flush)
+            + share_royal
+            + share_max(Self::run_len(self.rank_mask(), card.rank()), prize_straight)
+            + share_max(self.suit_count(card.suit()), prize_flush)
+            + share_max(self.rank_count(card.rank()), prize_n_of_kind)
+    }
+
+    fn suit_count(&self, suit: CardSuit) -> usize {
+        self.0[suit.to_index()].count_ones() as usize
+    }
+
+    fn rank_count(&self, rank: CardRank) -> usize {
+        self.0
+            .iter()
+            .filter(|&&mask| mask & (1 << rank.to_index()) != 0)
+            .count()
+    }
+
+    fn rank_mask(&self) -> u16 {
+        self.0.iter().fold(0, |acc, mask| acc | mask)
+    }
+
+    /// ランク集合 `mask` 内で `rank` を含む巡回的な連番の長さを返す (5 で頭打ち)。
+    fn run_len(mask: u16, rank: CardRank) -> usize {
+        let i = rank.to_index();
+        if mask & (1 << i) == 0 {
+            return 0;
+        }
+        let has = |d: usize| mask & (1 << ((i + d) % CardRank::NUM)) != 0;
+        let fwd = (1..5).take_while(|&d| has(d)).count();
+        let bwd = (1..5).take_while(|&d| has(CardRank::NUM - d)).count();
+        (1 + fwd + bwd).min(5)
+    }
+}
+
+/// 役検出結果から成立した全ての役を列挙し、それぞれについて `f` を呼ぶ。
+///
+/// 各ライン上では各種類の役は高々 1 つしか成立しない (先に見つかったものが優先される)。
+/// ストレートフラッシュはストレートおよびフラッシュとしても列挙される。
+/// ロイヤルフラッシュは 5 枚ストレートフラッシュとは別の役として列挙される。
+fn for_each_hand(board: &Board, yaku_board: &YakuBoard, mut f: impl FnMut(Hand)) {
+    for row in Row::all() {
+        let line = YakuLine::Row(row);
+        let ary = yaku_board.row(row);
+        if let Some(hand) = find_hand(
+            HandKind::StraightFlush,
+            line,
+            &ary,
+            YakuMask::has_straight_flush,
+        ) {
+            f(hand);
+            if hand.len == 5 {
+                let ranks = board.row(row).map(|card| card.unwrap().rank());
+                if ranks_is_royal(&ranks) {
+                    f(Hand {
+                        kind: HandKind::RoyalFlush,
+                        ..hand
+                    });
+                }
+            }
+        }
+    }
+
+    // NOTE: 列については 5 枚ストレートフラッシュは出現しえない。よってロイヤルフラッシュもありえない。
+    for col in Col::all() {
+        let line = YakuLine::Col(col);
+        let ary = yaku_board.col(col);
+        if let Some(hand) = find_hand(
+            HandKind::StraightFlush,
+            line,
+            &ary,
+            YakuMask::has_straight_flush,
+        ) {
+            f(hand);
+        }
+    }
+
+    for kind in [HandKind::Straight, HandKind::Flush, HandKind::NOfKind] {
+        let cond = match kind {
+            HandKind::Straight => YakuMask::has_straight,
+            HandKind::Flush => YakuMask::has_flush,
+            _ => YakuMask::has_n_of_kind,
+        };
+        for row in Row::all() {
+            if let Some(hand) = find_hand(kind, YakuLine::Row(row), &yaku_board.row(row), cond) {
+                f(hand);
+            }
+        }
+        for col in Col::all() {
+            if let Some(hand) = find_hand(kind, YakuLine::Col(col), &yaku_board.col(col), cond) {
+                f(hand);
+            }
+        }
+    }
+}
+
+/// 1 つのライン上の役検出結果から、条件を満たすものが 3 つ以上並ぶ最初の区間を役として返す。
+fn find_hand(
+    kind: HandKind,
+    line: YakuLine,
+    ary: &[YakuMask; 5],
+    cond: impl Fn(YakuMask) -> bool,
+) -> Option<Hand> {
+    (0..3).find_map(|start| {
+        let len = yaku_len(&ary[start..], &cond);
+        (len >= 3).then_some(Hand {
+            kind,
+            line,
+            start,
+            len,
+        })
+    })
+}
+
+/// ランク配列がロイヤルフラッシュの条件を満たすかどうかを返す。
+fn ranks_is_royal(ranks: &[CardRank; 5]) -> bool {
+    matches!(
+        ranks,
+        [RANK_T, RANK_J, RANK_Q, RANK_K, RANK_A] | [RANK_A, RANK_K, RANK_Q, RANK_J, RANK_T]
+    )
+}
+
+/// 役の種類。
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum HandKind {
+    StraightFlush,
+    /// ロイヤルフラッシュ。5 枚ストレートフラッシュに追加で成立する。
+    RoyalFlush,
+    Straight,
+    Flush,
+    /// スリーカード/フォーカード。
+    NOfKind,
+}
+
+/// 役が成立するライン (行または列)。
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum YakuLine {
+    Row(Row),
+    Col(Col),
+}
+
+/// 成立した役。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Hand {
+    kind: HandKind,
+    line: YakuLine,
+    start: usize,
+    len: usize,
+}
+
+impl Hand {
+    pub fn kind(self) -> HandKind {
+        self.kind
+    }
+
+    pub fn line(self) -> YakuLine {
+        self.line
+    }
+
+    /// 役を構成するカードの枚数を返す。
+    pub fn card_count(self) -> usize {
+        self.len
+    }
+
+    /// 役を構成するマスたちを列挙する。行については左から、列については下から順に列挙する。
+    pub fn squares(self) -> impl Iterator<Item = Square> {
+        (self.start..self.start + self.len).map(move |i| match self.line {
+            YakuLine::Row(row) => Square::new(Col::all()[i], row),
+            YakuLine::Col(col) => Square::new(col, Row::all()[i]),
+        })
+    }
+
+    /// 倍率を掛ける前の賞金を返す。
+    pub fn prize(self) -> Money {
+        match self.kind {
+            HandKind::StraightFlush => prize_straight_flush(self.len),
+            HandKind::RoyalFlush => PRIZE_ROYAL_FLUSH,
+            HandKind::Straight => prize_straight(self.len),
+            HandKind::Flush => prize_flush(self.len),
+            HandKind::NOfKind => prize_n_of_kind(self.len),
+        }
+    }
+}
+
 /// 与えられた役検出結果スライスの先頭から条件を満たすものの個数を返す。
 fn yaku_len(ary: &[YakuMask], cond: impl Fn(YakuMask) -> bool) -> usize {
     ary.iter()
@@ -585,14 +1013,15 @@ fn rows_from(row: Row) -> impl Iterator<Item = Row> {
 
 /// 役検出結果を要素とする盤面。
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
-struct YakuBoard([YakuMask; Col::NUM * Row::NUM]);
+pub struct YakuBoard([YakuMask; Col::NUM * Row::NUM]);
 
 impl YakuBoard {
     fn new() -> Self {
         Self::default()
     }
 
-    fn col(&self, col: Col) -> [YakuMask; 5] {
+    /// 列 `col` の役検出結果を下から順に返す。
+    pub fn col(&self, col: Col) -> [YakuMask; 5] {
         unsafe {
             self.0[5 * col.to_index()..][..5]
                 .try_into()
@@ -600,15 +1029,18 @@ impl YakuBoard {
         }
     }
 
-    fn row(&self, row: Row) -> [YakuMask; 5] {
+    /// 行 `row` の役検出結果を左から順に返す。
+    pub fn row(&self, row: Row) -> [YakuMask; 5] {
         std::array::from_fn(|col| self.0[5 * col + row.to_index()])
     }
 
-    fn count_nonzero(&self) -> usize {
+    /// 何らかの役に絡んだマスの数を返す。
+    pub fn count_nonzero(&self) -> usize {
         self.0.iter().filter(|mask| !mask.is_zero()).count()
     }
 
-    fn squares_nonzero(&self) -> impl Iterator<Item = Square> + '_ {
+    /// 何らかの役に絡んだマスたちを列挙する。
+    pub fn squares_nonzero(&self) -> impl Iterator<Item = Square> + '_ {
         Square::all().into_iter().filter(|&sq| !self[sq].is_zero())
     }
 }
@@ -627,24 +1059,25 @@ impl std::ops::IndexMut<Square> for YakuBoard {
     }
 }
 
+/// 1 マスの役検出結果。そのマスのカードが絡んだ役の種類のフラグたち。
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-struct YakuMask(u8);
+pub struct YakuMask(u8);
 
 impl YakuMask {
     const BIT_STRAIGHT: u8 = 1 << 0;
     const BIT_FLUSH: u8 = 1 << 1;
     const BIT_N_OF_KIND: u8 = 1 << 2;
 
-    #[allow(dead_code)]
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self::default()
     }
 
-    fn is_zero(self) -> bool {
+    /// どの役にも絡んでいないかどうかを返す。
+    pub fn is_zero(self) -> bool {
         self.0 == 0
     }
 
-    fn has_straight(self) -> bool {
+    pub fn has_straight(self) -> bool {
         (self.0 & Self::BIT_STRAIGHT) != 0
     }
 
@@ -652,7 +1085,7 @@ impl YakuMask {
         self.0 |= Self::BIT_STRAIGHT;
     }
 
-    fn has_flush(self) -> bool {
+    pub fn has_flush(self) -> bool {
         (self.0 & Self::BIT_FLUSH) != 0
     }
 
@@ -660,7 +1093,7 @@ impl YakuMask {
         self.0 |= Self::BIT_FLUSH;
     }
 
-    fn has_n_of_kind(self) -> bool {
+    pub fn has_n_of_kind(self) -> bool {
         (self.0 & Self::BIT_N_OF_KIND) != 0
     }
 
@@ -668,7 +1101,7 @@ impl YakuMask {
         self.0 |= Self::BIT_N_OF_KIND
     }
 
-    fn has_straight_flush(self) -> bool {
+    pub fn has_straight_flush(self) -> bool {
         self.has_straight() && self.has_flush()
     }
 }
@@ -679,6 +1112,9 @@ mod tests {
 
     use indoc::indoc;
 
+    use crate::frame_model::LinearFrameModel;
+    use crate::square::*;
+
     use super::*;
 
     fn parse_board(s: impl AsRef<str>) -> Board {
@@ -687,8 +1123,8 @@ mod tests {
 
     fn yaku_step(board: impl Borrow<Board>) -> (Board, Money) {
         let mut after = board.borrow().clone();
-        let (_frame, prize) = process_yaku_step(&mut after);
-        (after, prize)
+        let step = process_yaku_step(&mut after, &LinearFrameModel::DEFAULT, 0);
+        (after, step.prize)
     }
 
     #[test]
@@ -1043,7 +1479,111 @@ mod tests {
         for (before, after, prize) in cases {
             let before = parse_board(before);
             let after = parse_board(after);
-            assert_eq!(yaku_step(before), (after, prize));
+            assert_eq!(yaku_step(&before), (after.clone(), prize));
+
+            let mut board = before.clone();
+            let event = process_yaku_event(&mut board, &LinearFrameModel::DEFAULT, 0).unwrap();
+            assert_eq!(board, after);
+            assert_eq!(event.prize(), prize);
+            assert_eq!(
+                event.prize_base(),
+                event.hands().iter().map(|hand| hand.prize()).sum::<Money>()
+            );
+            assert_eq!(
+                event.cards_removed().len(),
+                before.card_count() - after.card_count()
+            );
         }
     }
+
+    #[test]
+    fn test_process_yaku_chain_detail() {
+        let model = LinearFrameModel::DEFAULT;
+
+        let mut board = Board::new();
+        let chain = process_yaku_chain_detail(&mut board, &model);
+        assert_eq!(chain.depth(), 0);
+        assert_eq!(chain.prize(), 0);
+
+        // 昇順ロイヤルフラッシュ
+        let mut board = parse_board(indoc! {"
+            ..........
+            ..........
+            ..........
+            CAH3..H7C9
+            STSJSQSKSA
+        "});
+        let mut board_plain = board.clone();
+        let chain = process_yaku_chain_detail(&mut board, &model);
+        assert_eq!(
+            (chain.frame(), chain.prize()),
+            process_yaku_chain(&mut board_plain, &model)
+        );
+        assert_eq!(board, board_plain);
+
+        let [event] = chain.events() else {
+            panic!("{chain:?}");
+        };
+        let kinds: Vec<_> = event.hands().iter().map(|hand| hand.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                HandKind::StraightFlush,
+                HandKind::RoyalFlush,
+                HandKind::Straight,
+                HandKind::Flush
+            ]
+        );
+        for hand in event.hands() {
+            assert_eq!(hand.line(), YakuLine::Row(ROW_1));
+            assert_eq!(hand.card_count(), 5);
+            assert!(hand.squares().eq([SQ_A1, SQ_B1, SQ_C1, SQ_D1, SQ_E1]));
+        }
+        assert_eq!(event.multiplier(), 1);
+        assert_eq!(
+            event.prize(),
+            PRIZE_ROYAL_FLUSH + PRIZE_STRAIGHT_FLUSH_5 + PRIZE_STRAIGHT_5 + PRIZE_FLUSH_5
+        );
+        assert_eq!(event.cards_removed().len(), 5);
+    }
+
+    #[test]
+    fn test_detect_hands() {
+        assert!(detect_hands(&Board::new()).is_empty());
+        assert_eq!(detect_yaku(&Board::new()).count_nonzero(), 0);
+
+        // 行のスリーカードと列の 3 枚フラッシュ
+        let board = parse_board(indoc! {"
+            ..........
+            ..........
+            ......S9..
+            ......S7..
+            H3D3C3S2..
+        "});
+        let board_orig = board.clone();
+
+        let yaku_board = detect_yaku(&board);
+        assert_eq!(board, board_orig);
+        assert_eq!(yaku_board.count_nonzero(), 6);
+        assert!(yaku_board[SQ_A1].has_n_of_kind());
+        assert!(!yaku_board[SQ_A1].has_flush());
+        assert!(yaku_board[SQ_D1].has_flush());
+        assert!(yaku_board[SQ_D3].has_flush());
+        assert!(yaku_board[SQ_E1].is_zero());
+
+        let hands = detect_hands(&board);
+        assert_eq!(board, board_orig);
+        assert_eq!(hands.len(), 2);
+        assert_eq!(hands[0].kind(), HandKind::Flush);
+        assert_eq!(hands[0].line(), YakuLine::Col(COL_D));
+        assert!(hands[0].squares().eq([SQ_D1, SQ_D2, SQ_D3]));
+        assert_eq!(hands[1].kind(), HandKind::NOfKind);
+        assert_eq!(hands[1].line(), YakuLine::Row(ROW_1));
+        assert!(hands[1].squares().eq([SQ_A1, SQ_B1, SQ_C1]));
+
+        assert_eq!(
+            calc_prize(&board, &yaku_board),
+            (PRIZE_FLUSH_3 + PRIZE_THREE_OF_KIND) * 2
+        );
+    }
 }